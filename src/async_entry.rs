@@ -0,0 +1,466 @@
+/*!
+
+# Async entries
+
+All the methods on an [Entry] block until the underlying credential store
+has finished the requested operation, and for some stores (such as the
+dbus-based secret service) that can take a noticeable amount of time.
+Calling them from inside an async task will stall the executor running
+that task.
+
+The [AsyncEntry] type in this module wraps an [Entry] and provides `async`
+versions of all its store operations.  Each operation is run on a
+separate thread, and the returned future completes when the operation
+does, so the executor is never blocked.  Because the thread is a plain
+OS thread, this works with any executor (tokio, async-std, smol, etc.)
+and doesn't require the client to call their executor's equivalent of
+`spawn_blocking`.
+
+The threads come from a small pool shared by all async entries.
+At most four operations run at once; any others wait in a queue until a
+thread is free, so a burst of async calls doesn't start a burst of threads.
+Pool threads are started as they are needed and exit after
+they have been idle for a few seconds.
+
+Async entries are created from the same credential builders as
+entries (including the mock builder), so they work with every store:
+```rust
+# use keyring::{async_entry::AsyncEntry, mock};
+# keyring::set_default_credential_builder(mock::default_credential_builder());
+# let _ = async {
+let entry = AsyncEntry::new("my-service", "my-name")?;
+entry.set_password("topS3cr3tP4$$w0rd").await?;
+let password = entry.get_password().await?;
+entry.delete_credential().await?;
+# keyring::Result::Ok(())
+# };
+```
+
+If a store operation panics, the panic is propagated to the task
+that awaits the operation's result.
+ */
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use log::debug;
+
+use super::{Credential, Entry, Result, SecretBytes, SecretString};
+
+/// An [Entry] whose store operations are `async`.
+///
+/// Async entries are cheap to clone: all the clones share the same
+/// underlying entry.
+#[derive(Debug, Clone)]
+pub struct AsyncEntry {
+    inner: Arc<Entry>,
+}
+
+impl AsyncEntry {
+    /// Create an async entry for the given service and user.
+    ///
+    /// The default credential builder is used. Creating an entry
+    /// doesn't access the credential store, so this call doesn't block.
+    pub fn new(service: &str, user: &str) -> Result<AsyncEntry> {
+        Ok(Entry::new(service, user)?.into())
+    }
+
+    /// Create an async entry for the given target, service, and user.
+    ///
+    /// The default credential builder is used. Creating an entry
+    /// doesn't access the credential store, so this call doesn't block.
+    pub fn new_with_target(target: &str, service: &str, user: &str) -> Result<AsyncEntry> {
+        Ok(Entry::new_with_target(target, service, user)?.into())
+    }
+
+    /// Create an async entry from a credential that may be in any credential store.
+    pub fn new_with_credential(credential: Box<Credential>) -> AsyncEntry {
+        Entry::new_with_credential(credential).into()
+    }
+
+    /// Return the (blocking) entry wrapped by this async entry.
+    pub fn entry(&self) -> &Entry {
+        &self.inner
+    }
+
+    /// Set the password for this entry.
+    ///
+    /// See [Entry::set_password] for details.
+    pub async fn set_password(&self, password: &str) -> Result<()> {
+        let password = SecretString::new(password.to_string());
+        self.spawn(move |entry| entry.set_password(password.expose_secret()))
+            .await
+    }
+
+    /// Set the secret for this entry.
+    ///
+    /// See [Entry::set_secret] for details.
+    pub async fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let secret = SecretBytes::new(secret.to_vec());
+        self.spawn(move |entry| entry.set_secret(secret.expose_secret()))
+            .await
+    }
+
+    /// Retrieve the password saved for this entry.
+    ///
+    /// See [Entry::get_password] for details.
+    pub async fn get_password(&self) -> Result<String> {
+        self.spawn(|entry| entry.get_password()).await
+    }
+
+    /// Retrieve the secret saved for this entry.
+    ///
+    /// See [Entry::get_secret] for details.
+    pub async fn get_secret(&self) -> Result<Vec<u8>> {
+        self.spawn(|entry| entry.get_secret()).await
+    }
+
+    /// Retrieve the password saved for this entry, in a buffer that is zeroed on drop.
+    ///
+    /// See [Entry::get_zeroizing_password] for details.
+    pub async fn get_zeroizing_password(&self) -> Result<SecretString> {
+        self.spawn(|entry| entry.get_zeroizing_password()).await
+    }
+
+    /// Retrieve the secret saved for this entry, in a buffer that is zeroed on drop.
+    ///
+    /// See [Entry::get_zeroizing_secret] for details.
+    pub async fn get_zeroizing_secret(&self) -> Result<SecretBytes> {
+        self.spawn(|entry| entry.get_zeroizing_secret()).await
+    }
+
+    /// Get the attributes on the underlying credential for this entry.
+    ///
+    /// See [Entry::get_attributes] for details.
+    pub async fn get_attributes(&self) -> Result<HashMap<String, String>> {
+        self.spawn(|entry| entry.get_attributes()).await
+    }
+
+    /// Update the attributes on the underlying credential for this entry.
+    ///
+    /// See [Entry::update_attributes] for details.
+    pub async fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
+        let attributes: HashMap<String, String> = attributes
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        self.spawn(move |entry| {
+            let attributes: HashMap<&str, &str> = attributes
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+            entry.update_attributes(&attributes)
+        })
+        .await
+    }
+
+    /// Delete the underlying credential for this entry.
+    ///
+    /// See [Entry::delete_credential] for details.
+    pub async fn delete_credential(&self) -> Result<()> {
+        self.spawn(|entry| entry.delete_credential()).await
+    }
+
+    /// Check whether the storage for this entry's credential is locked.
+    ///
+    /// See [Entry::is_locked] for details.
+    pub async fn is_locked(&self) -> Result<bool> {
+        self.spawn(|entry| entry.is_locked()).await
+    }
+
+    /// Unlock the storage for this entry's credential.
+    ///
+    /// See [Entry::unlock] for details.
+    pub async fn unlock(&self) -> Result<()> {
+        self.spawn(|entry| entry.unlock()).await
+    }
+
+    /// Lock the storage for this entry's credential.
+    ///
+    /// See [Entry::lock] for details.
+    pub async fn lock(&self) -> Result<()> {
+        self.spawn(|entry| entry.lock()).await
+    }
+
+    /// Run a blocking operation on this entry in a pool thread.
+    fn spawn<T, F>(&self, f: F) -> BlockingTask<Result<T>>
+    where
+        F: FnOnce(&Entry) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let entry = self.inner.clone();
+        BlockingTask::spawn(move || f(&entry))
+    }
+}
+
+impl From<Entry> for AsyncEntry {
+    fn from(entry: Entry) -> Self {
+        Self {
+            inner: Arc::new(entry),
+        }
+    }
+}
+
+/// The state shared between a blocking task's thread and its future.
+struct TaskState<T> {
+    result: Option<std::thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// A future that completes when a function run on a pool thread returns.
+struct BlockingTask<T> {
+    state: Arc<Mutex<TaskState<T>>>,
+}
+
+impl<T: Send + 'static> BlockingTask<T> {
+    fn spawn<F>(f: F) -> Self
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(TaskState {
+            result: None,
+            waker: None,
+        }));
+        let thread_state = state.clone();
+        pool().submit(Box::new(move || {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+            let mut state = thread_state
+                .lock()
+                .expect("Poisoned async task state in keyring-rs: please report a bug!");
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake()
+            }
+        }));
+        Self { state }
+    }
+}
+
+impl<T> Future for BlockingTask<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Poisoned async task state in keyring-rs: please report a bug!");
+        match state.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(panic)) => {
+                debug!("async keyring operation panicked; resuming panic in awaiting task");
+                std::panic::resume_unwind(panic)
+            }
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// The most pool threads that can be running at once.
+const MAX_POOL_THREADS: usize = 4;
+
+/// How long a pool thread waits for work before it exits.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The queue of jobs waiting for a pool thread, and the pool's thread counts.
+#[derive(Default)]
+struct PoolState {
+    jobs: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+}
+
+/// The thread pool that runs blocking tasks.
+#[derive(Default)]
+struct Pool {
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+/// The pool shared by all async entries.
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(Pool::default)
+}
+
+impl Pool {
+    /// Queue a job, starting a new thread for it if all the running ones
+    /// are busy and the pool isn't full.
+    fn submit(&'static self, job: Job) {
+        let mut state = self.lock();
+        state.jobs.push_back(job);
+        if state.jobs.len() > state.idle && state.threads < MAX_POOL_THREADS {
+            state.threads += 1;
+            std::thread::Builder::new()
+                .name("keyring-async".to_string())
+                .spawn(move || self.work())
+                .expect("Can't start an async keyring thread");
+        } else {
+            self.available.notify_one();
+        }
+    }
+
+    /// Run queued jobs until none have arrived for [POOL_IDLE_TIMEOUT].
+    fn work(&self) {
+        let mut state = self.lock();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                drop(state);
+                // jobs catch their own panics, so this never unwinds
+                job();
+                state = self.lock();
+                continue;
+            }
+            state.idle += 1;
+            let (next, timeout) = self
+                .available
+                .wait_timeout(state, POOL_IDLE_TIMEOUT)
+                .expect("Poisoned async thread pool in keyring-rs: please report a bug!");
+            state = next;
+            state.idle -= 1;
+            if timeout.timed_out() && state.jobs.is_empty() {
+                state.threads -= 1;
+                return;
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        self.state
+            .lock()
+            .expect("Poisoned async thread pool in keyring-rs: please report a bug!")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsyncEntry;
+    use crate::{Entry, Error, mock, testing::generate_random_string};
+    use std::collections::HashMap;
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    /// A minimal executor, so the tests don't depend on any particular runtime.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(std::thread::Thread);
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark()
+            }
+        }
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    fn entry_new(service: &str, user: &str) -> AsyncEntry {
+        let credential = mock::default_credential_builder()
+            .build(None, service, user)
+            .expect("Can't build mock credential");
+        AsyncEntry::new_with_credential(credential)
+    }
+
+    #[test]
+    fn test_round_trip_password() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        block_on(async {
+            assert!(matches!(entry.get_password().await, Err(Error::NoEntry)));
+            entry.set_password("async password").await.unwrap();
+            assert_eq!(entry.get_password().await.unwrap(), "async password");
+            entry.delete_credential().await.unwrap();
+            assert!(matches!(entry.get_password().await, Err(Error::NoEntry)));
+        })
+    }
+
+    #[test]
+    fn test_round_trip_secret_and_attributes() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        let secret = [0u8, 1, 2, 255];
+        block_on(async {
+            entry.set_secret(&secret).await.unwrap();
+            assert_eq!(entry.get_secret().await.unwrap(), secret);
+            let zeroizing = entry.get_zeroizing_secret().await.unwrap();
+            assert_eq!(zeroizing.expose_secret(), secret);
+            let attributes = HashMap::from([("name", "value")]);
+            entry.update_attributes(&attributes).await.unwrap();
+            entry.get_attributes().await.unwrap();
+            entry.delete_credential().await.unwrap();
+        })
+    }
+
+    #[test]
+    fn test_errors_are_returned() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        let mock: &mock::MockCredential = entry.entry().get_credential().downcast_ref().unwrap();
+        mock.set_error(Error::Invalid("mock".to_string(), "error".to_string()));
+        block_on(async {
+            assert!(matches!(
+                entry.set_password("fails").await,
+                Err(Error::Invalid(_, _))
+            ));
+            entry.set_password("succeeds").await.unwrap();
+        })
+    }
+
+    #[test]
+    fn test_pool_is_bounded() {
+        use super::{BlockingTask, MAX_POOL_THREADS};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..3 * MAX_POOL_THREADS)
+            .map(|i| {
+                let (running, most) = (running.clone(), most.clone());
+                BlockingTask::spawn(move || {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i
+                })
+            })
+            .collect();
+        for (i, task) in tasks.into_iter().enumerate() {
+            assert_eq!(block_on(task), i);
+        }
+        let most = most.load(Ordering::SeqCst);
+        assert!(most <= MAX_POOL_THREADS, "{most} tasks ran at once");
+    }
+
+    #[test]
+    fn test_from_entry_shares_credential() {
+        let name = generate_random_string();
+        let entry: Entry = Entry::new_with_credential(
+            mock::default_credential_builder()
+                .build(None, &name, &name)
+                .unwrap(),
+        );
+        entry.set_password("blocking").unwrap();
+        let async_entry = AsyncEntry::from(entry);
+        let clone = async_entry.clone();
+        block_on(async {
+            assert_eq!(clone.get_password().await.unwrap(), "blocking");
+            async_entry.delete_credential().await.unwrap();
+            assert!(matches!(clone.get_password().await, Err(Error::NoEntry)));
+        })
+    }
+}
@@ -0,0 +1,334 @@
+/*!
+
+# Choosing the default credential store at runtime
+
+When the `config` feature is specified, the credential builder used by default
+to create entries (see [default_credential_builder](crate::default_credential_builder))
+can be chosen when a program runs, rather than when it is built.  This allows the
+same binary to use the platform's secure store on desktops, the mock store
+in CI, and no store at all on headless servers, without any code in the client
+to choose between them.
+
+The store is chosen as follows:
+
+1. If the `KEYRING_BACKEND` environment variable is set, its value names the store.
+2. Otherwise, if the `KEYRING_CONFIG` environment variable is set, its value
+   is the path of a TOML file that names the store and (optionally) gives options for it.
+3. Otherwise, the platform's built-in store is used
+   (see [builtin_credential_builder](crate::builtin_credential_builder)).
+
+The store names are:
+
+- `builtin`: the platform's built-in store.
+- `mock`: the [mock](crate::mock) store.
+- `none`: no store; creating entries will fail with a
+  [NoDefaultCredentialBuilder](crate::Error::NoDefaultCredentialBuilder) error.
+- `secret-service`, `windows`, `macos`, `ios`: the named platform store,
+  if it's included in the build.
+
+You can also give a list of store names separated by commas, such as
+`secret-service,mock`, in which case the first of the listed stores that is
+available when the default builder is first used will be chosen
+(see the [fallback](crate::fallback) module).
+
+A config file has a top-level `backend` key that gives the store name (or list
+of names), and may have a table named after each store whose keys are options
+for that store.  Options that a store doesn't understand are an error, as is any
+other problem with the configuration.  For example:
+```toml
+backend = "secret-service,mock"
+
+[secret-service]
+encryption = "dh"
+```
+
+The only store option at present is the `encryption` option of the
+`secret-service` store, which is either `dh` (to encrypt secrets on the bus)
+or `plain`; see the [secret_service](crate::secret_service) module.
+
+If the configuration is invalid, [default_credential_builder](crate::default_credential_builder)
+logs the problem and uses the built-in store.  Clients who want to report
+configuration errors themselves can call [configured_credential_builder] at startup.
+ */
+use std::collections::HashMap;
+
+use super::fallback::FallbackCredentialBuilder;
+use super::{CredentialBuilder, Error, Result};
+
+/// The environment variable that names the default credential store.
+pub const BACKEND_VAR: &str = "KEYRING_BACKEND";
+
+/// The environment variable that gives the path of the config file.
+pub const CONFIG_VAR: &str = "KEYRING_CONFIG";
+
+/// A choice of credential store and its options.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackendConfig {
+    /// The name of the store, or a comma-separated list of store names
+    pub backend: String,
+    /// The options for each store, keyed by store name, with numbers
+    /// and booleans converted to their string representation
+    pub options: HashMap<String, HashMap<String, String>>,
+}
+
+impl BackendConfig {
+    /// A configuration that names the given store (or stores), with no options.
+    pub fn new(backend: &str) -> Self {
+        Self {
+            backend: backend.to_string(),
+            options: HashMap::new(),
+        }
+    }
+
+    /// Read the configuration from the environment (see the module docs).
+    ///
+    /// Returns `None` if neither environment variable is set.
+    pub fn from_env() -> Result<Option<Self>> {
+        if let Ok(backend) = std::env::var(BACKEND_VAR) {
+            return Ok(Some(Self::new(backend.trim())));
+        }
+        let Some(path) = std::env::var_os(CONFIG_VAR) else {
+            return Ok(None);
+        };
+        let text = std::fs::read_to_string(&path).map_err(|err| {
+            Error::Invalid(
+                CONFIG_VAR.to_string(),
+                format!("can't read {}: {err}", path.to_string_lossy()),
+            )
+        })?;
+        Ok(Some(Self::from_toml(&text)?))
+    }
+
+    /// Parse the configuration from the text of a config file.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::Invalid(CONFIG_VAR.to_string(), reason);
+        let table: toml::Table = text.parse().map_err(|err| invalid(format!("{err}")))?;
+        let backend = match table.get("backend") {
+            Some(toml::Value::String(backend)) => backend.clone(),
+            Some(_) => return Err(invalid("backend must be a string".to_string())),
+            None => return Err(invalid("no backend is specified".to_string())),
+        };
+        let mut options = HashMap::new();
+        for name in store_names(&backend) {
+            let store_options = match table.get(name) {
+                Some(toml::Value::Table(store_options)) => store_options
+                    .iter()
+                    .map(|(key, value)| match value {
+                        toml::Value::String(s) => Ok((key.clone(), s.clone())),
+                        toml::Value::Integer(i) => Ok((key.clone(), i.to_string())),
+                        toml::Value::Float(f) => Ok((key.clone(), f.to_string())),
+                        toml::Value::Boolean(b) => Ok((key.clone(), b.to_string())),
+                        _ => Err(invalid(format!("option {key} must be a simple value"))),
+                    })
+                    .collect::<Result<_>>()?,
+                Some(_) => return Err(invalid(format!("{name} options must be a table"))),
+                None => continue,
+            };
+            options.insert(name.to_string(), store_options);
+        }
+        Ok(Self { backend, options })
+    }
+
+    /// Build the configured credential builder.
+    ///
+    /// If more than one store is named, this is a
+    /// [FallbackCredentialBuilder] with the named stores as candidates.
+    pub fn build(&self) -> Result<Box<CredentialBuilder>> {
+        if let Some(name) = self
+            .options
+            .keys()
+            .find(|name| !store_names(&self.backend).any(|n| n == name.as_str()))
+        {
+            return Err(Error::Invalid(
+                name.clone(),
+                "options are given for a store that isn't used".to_string(),
+            ));
+        }
+        let mut builders = store_names(&self.backend)
+            .map(|name| self.build_store(name))
+            .collect::<Result<Vec<_>>>()?;
+        match builders.len() {
+            0 => Err(Error::Invalid(
+                "backend".to_string(),
+                "no store is named".to_string(),
+            )),
+            1 => Ok(builders.remove(0)),
+            _ => Ok(Box::new(FallbackCredentialBuilder::new(builders))),
+        }
+    }
+
+    /// Build the builder for one named store, with its options.
+    fn build_store(&self, name: &str) -> Result<Box<CredentialBuilder>> {
+        let options = self.options.get(name).cloned().unwrap_or_default();
+        let (builder, options) = match name {
+            "builtin" => (crate::builtin_credential_builder(), options),
+            "mock" => (crate::mock::default_credential_builder(), options),
+            "none" => (crate::credential::nop_credential_builder(), options),
+            #[cfg(all(
+                any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"),
+                feature = "secret-service"
+            ))]
+            "secret-service" => secret_service_builder(options)?,
+            #[cfg(all(target_os = "windows", feature = "windows-native"))]
+            "windows" => (crate::windows::default_credential_builder(), options),
+            #[cfg(all(target_os = "macos", feature = "apple-native"))]
+            "macos" => (crate::macos::default_credential_builder(), options),
+            #[cfg(all(target_os = "ios", feature = "apple-native"))]
+            "ios" => (crate::ios::default_credential_builder(), options),
+            other => {
+                return Err(Error::Invalid(
+                    "backend".to_string(),
+                    format!("{other} is not a known credential store in this build"),
+                ));
+            }
+        };
+        if let Some(option) = options.keys().next() {
+            return Err(Error::Invalid(
+                option.clone(),
+                format!("not an option of the {name} store"),
+            ));
+        }
+        Ok(builder)
+    }
+}
+
+/// Build a secret-service builder from the options it understands,
+/// returning the builder and the options it doesn't.
+#[cfg(all(
+    any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"),
+    feature = "secret-service"
+))]
+fn secret_service_builder(
+    mut options: HashMap<String, String>,
+) -> Result<(Box<CredentialBuilder>, HashMap<String, String>)> {
+    use crate::secret_service::{EncryptionType, SsCredentialBuilder};
+    let encryption = match options.remove("encryption").as_deref() {
+        None => return Ok((crate::secret_service::default_credential_builder(), options)),
+        Some("dh") => EncryptionType::Dh,
+        Some("plain") => EncryptionType::Plain,
+        Some(other) => {
+            return Err(Error::Invalid(
+                "encryption".to_string(),
+                format!("{other} is not dh or plain"),
+            ));
+        }
+    };
+    let builder = SsCredentialBuilder::with_encryption(encryption);
+    Ok((Box::new(builder), options))
+}
+
+/// The store names in a comma-separated list, without surrounding whitespace.
+fn store_names(backend: &str) -> impl Iterator<Item = &str> {
+    backend
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+/// Build the credential builder specified by the environment, if any.
+///
+/// Returns `None` if the environment doesn't specify a store.
+/// See the module docs for details.
+pub fn configured_credential_builder() -> Result<Option<Box<CredentialBuilder>>> {
+    match BackendConfig::from_env()? {
+        Some(config) => Ok(Some(config.build()?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BackendConfig;
+    use crate::Error;
+    use crate::fallback::FallbackCredentialBuilder;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse() {
+        let config = BackendConfig::from_toml("backend = \"mock\"\n").unwrap();
+        assert_eq!(config, BackendConfig::new("mock"));
+        let text = "backend = \"none\"\n[none]\nname = \"value\"\ncount = 3\n[mock]\nother = 1\n";
+        let config = BackendConfig::from_toml(text).unwrap();
+        assert_eq!(config.backend, "none");
+        assert_eq!(config.options.len(), 1);
+        assert_eq!(config.options["none"]["name"], "value");
+        assert_eq!(config.options["none"]["count"], "3");
+        let text = "backend = \"none, mock\"\n[mock]\nother = 1\n";
+        let config = BackendConfig::from_toml(text).unwrap();
+        assert_eq!(config.options["mock"]["other"], "1");
+        for bad in [
+            "",
+            "backend = 3",
+            "backend = \"mock\"\nmock = 1",
+            "backend = ",
+            "backend = \"mock\"\n[mock]\nlist = [1]",
+        ] {
+            assert!(
+                matches!(BackendConfig::from_toml(bad), Err(Error::Invalid(_, _))),
+                "Config {bad:?} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn test_build() {
+        let builder = BackendConfig::new("mock").build().unwrap();
+        assert!(builder.as_any().is::<crate::mock::MockCredentialBuilder>());
+        let builder = BackendConfig::new("none").build().unwrap();
+        assert!(matches!(
+            builder.build(None, "service", "user"),
+            Err(Error::NoDefaultCredentialBuilder)
+        ));
+        BackendConfig::new("builtin").build().unwrap();
+        for bad in ["no-such-store", " , ", "mock,no-such-store"] {
+            assert!(
+                matches!(BackendConfig::new(bad).build(), Err(Error::Invalid(_, _))),
+                "Backend {bad:?} was accepted"
+            );
+        }
+        let options = HashMap::from([("color".to_string(), "blue".to_string())]);
+        let mut config = BackendConfig::new("mock");
+        config.options.insert("mock".to_string(), options.clone());
+        assert!(matches!(config.build(), Err(Error::Invalid(_, _))));
+        let mut config = BackendConfig::new("mock");
+        config.options.insert("none".to_string(), options);
+        assert!(matches!(config.build(), Err(Error::Invalid(_, _))));
+    }
+
+    #[test]
+    #[cfg(all(
+        any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"),
+        feature = "secret-service"
+    ))]
+    fn test_build_secret_service() {
+        use crate::secret_service::{EncryptionType, SsCredentialBuilder, default_encryption};
+        let encryption = |config: &BackendConfig| {
+            let builder = config.build().unwrap();
+            let builder: &SsCredentialBuilder = builder.as_any().downcast_ref().unwrap();
+            builder.encryption()
+        };
+        let mut config = BackendConfig::new("secret-service");
+        assert_eq!(encryption(&config), default_encryption());
+        for (value, expected) in [("dh", EncryptionType::Dh), ("plain", EncryptionType::Plain)] {
+            let options = HashMap::from([("encryption".to_string(), value.to_string())]);
+            config.options.insert("secret-service".to_string(), options);
+            assert_eq!(encryption(&config), expected);
+        }
+        for (name, value) in [("encryption", "aes"), ("color", "blue")] {
+            let options = HashMap::from([(name.to_string(), value.to_string())]);
+            config.options.insert("secret-service".to_string(), options);
+            assert!(
+                matches!(config.build(), Err(Error::Invalid(_, _))),
+                "Option {name} = {value} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_fallback() {
+        let builder = BackendConfig::new("none, mock").build().unwrap();
+        let fallback: &FallbackCredentialBuilder = builder.as_any().downcast_ref().unwrap();
+        assert_eq!(fallback.candidates().len(), 2);
+        assert_eq!(fallback.chosen_index(), 1);
+    }
+}
@@ -11,7 +11,8 @@ in a thread-safe way, a requirement captured in the [CredentialBuilder] and
 use std::any::Any;
 use std::collections::HashMap;
 
-use super::Result;
+use super::secret::{SecretBytes, SecretString, decode_zeroizing_password};
+use super::{Error, Result};
 
 /// The API that [credentials](Credential) implement.
 pub trait CredentialApi {
@@ -37,6 +38,55 @@ pub trait CredentialApi {
     /// for this entry, a [NoEntry](crate::Error::NoEntry) error is returned.
     fn get_secret(&self) -> Result<Vec<u8>>;
 
+    /// Retrieve the password from the credential in a buffer that is zeroed on drop.
+    ///
+    /// This behaves like [get_password](CredentialApi::get_password).
+    /// We provide a default implementation of this method
+    /// for backward compatibility with stores that don't implement it.
+    /// The default uses [get_zeroizing_secret](CredentialApi::get_zeroizing_secret),
+    /// so stores that implement that method needn't implement this one.
+    fn get_zeroizing_password(&self) -> Result<SecretString> {
+        decode_zeroizing_password(self.get_zeroizing_secret()?)
+    }
+
+    /// Retrieve the secret from the credential in a buffer that is zeroed on drop.
+    ///
+    /// This behaves like [get_secret](CredentialApi::get_secret).
+    /// We provide a default implementation of this method
+    /// for backward compatibility with stores that don't implement it.
+    /// The default takes ownership of the value returned by
+    /// [get_secret](CredentialApi::get_secret), so it can't zero any
+    /// intermediate copies made by the store.
+    fn get_zeroizing_secret(&self) -> Result<SecretBytes> {
+        Ok(SecretBytes::new(self.get_secret()?))
+    }
+
+    /// Set the credential's secret, but only if its current secret is the expected one.
+    ///
+    /// An `expected` value of `None` means the credential must not exist.
+    /// If the stored secret is not the expected one, a
+    /// [Conflict](crate::Error::Conflict) error is returned
+    /// and the secret is not changed.
+    ///
+    /// We provide a default implementation of this method
+    /// for backward compatibility with stores that don't implement it.
+    /// The default reads the current secret and then sets the new one,
+    /// so it is _not_ atomic: a write by another process between the read
+    /// and the set will be overwritten.  Stores that implement this method
+    /// atomically say so via their builder's
+    /// [atomic_updates](CredentialBuilderApi::atomic_updates) call.
+    fn set_secret_if(&self, expected: Option<&[u8]>, secret: &[u8]) -> Result<()> {
+        let current = match self.get_zeroizing_secret() {
+            Ok(current) => Some(current),
+            Err(Error::NoEntry) => None,
+            Err(err) => return Err(err),
+        };
+        if current.as_ref().map(|c| c.expose_secret()) != expected {
+            return Err(Error::Conflict);
+        }
+        self.set_secret(secret)
+    }
+
     /// Get the secure store attributes on this entry's credential.
     ///
     /// Each credential store may support reading and updating different
@@ -72,6 +122,55 @@ pub trait CredentialApi {
         Ok(())
     }
 
+    /// Whether the storage that holds this credential is locked, so that
+    /// using the credential needs the storage to be unlocked first.
+    ///
+    /// In some stores the lock covers many credentials (e.g., a whole collection).
+    /// Stores that can be locked have the `locking`
+    /// [capability](CredentialBuilderApi::capabilities).
+    ///
+    /// A default implementation is provided for backward compatibility,
+    /// since this API was added in a minor release.  The default is for
+    /// stores that can't be locked, so it always returns `false`.
+    fn is_locked(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Unlock the storage that holds this credential, which may prompt the user.
+    ///
+    /// A default implementation is provided for backward compatibility,
+    /// since this API was added in a minor release.  The default is for
+    /// stores that can't be locked, so it does nothing.
+    fn unlock(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Lock the storage that holds this credential.
+    ///
+    /// In some stores this also locks other credentials (e.g., a whole collection).
+    ///
+    /// A default implementation is provided for backward compatibility,
+    /// since this API was added in a minor release.  The default returns a
+    /// [NotSupportedByStore](crate::Error::NotSupportedByStore) error.
+    fn lock(&self) -> Result<()> {
+        Err(Error::NotSupportedByStore("lock".to_string()))
+    }
+
+    /// A builder whose [get_secrets_many](CredentialBuilderApi::get_secrets_many)
+    /// can fetch this credential's secret together with others.
+    ///
+    /// [Entry::get_secrets_many](crate::Entry::get_secrets_many) groups the
+    /// credentials whose batch builders have the same concrete type, and passes
+    /// each group to the batch builder of its first credential, so that builder
+    /// must be able to fetch credentials made by any other builder of its type.
+    ///
+    /// A default implementation is provided for backward compatibility,
+    /// since this API was added in a minor release.  The default returns `None`,
+    /// so the credential's secret is fetched by itself.
+    fn batch_builder(&self) -> Option<Box<CredentialBuilder>> {
+        None
+    }
+
     /// Delete the underlying credential, if there is one.
     ///
     /// This is not idempotent if the credential existed!
@@ -124,6 +223,90 @@ pub enum CredentialPersistence {
     UntilDelete,
 }
 
+/// A descriptor of what a credential store can do, returned from
+/// a credential store's [capabilities](CredentialBuilderApi::capabilities) call.
+///
+/// The default value describes a store that can't do anything
+/// beyond storing passwords, and has no known length limits.
+/// The length limits are on individual values, in bytes; stores
+/// may also limit the combined length of values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CredentialCapabilities {
+    /// Credentials have attributes that can be read and updated
+    pub attributes: bool,
+    /// Secrets can be arbitrary byte arrays rather than just UTF-8 strings
+    pub binary_secrets: bool,
+    /// Entries can have an empty service name
+    pub empty_service: bool,
+    /// Entries can have an empty user name
+    pub empty_user: bool,
+    /// The builder can [search](CredentialBuilderApi::search) for credentials
+    pub search: bool,
+    /// Conditional sets are atomic (see [atomic_updates](CredentialBuilderApi::atomic_updates))
+    pub atomic_updates: bool,
+    /// Credentials can be [locked](CredentialApi::lock) and [unlocked](CredentialApi::unlock)
+    pub locking: bool,
+    /// The maximum length of a target, if any
+    pub max_target_len: Option<usize>,
+    /// The maximum length of a service name, if any
+    pub max_service_len: Option<usize>,
+    /// The maximum length of a user name, if any
+    pub max_user_len: Option<usize>,
+    /// The maximum length of a secret, if any
+    pub max_secret_len: Option<usize>,
+}
+
+/// The availability of a credential store, as found by a
+/// credential store's [probe](CredentialBuilderApi::probe) call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StoreStatus {
+    /// The store can be used
+    Available,
+    /// The store can be reached, but it's locked, so using it may prompt the user
+    Locked,
+    /// The store can be reached, but it has nowhere to put new credentials
+    /// (e.g., there's no default collection)
+    NoDefaultStorage,
+    /// The store can be reached, but this process isn't allowed to use it
+    Inaccessible,
+    /// The store can't be reached (e.g., there's no D-Bus session),
+    /// or there is no store
+    Unreachable,
+}
+
+/// The result of a credential store's [probe](CredentialBuilderApi::probe) call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProbeReport {
+    /// The availability of the store
+    pub status: StoreStatus,
+    /// A description of what the probe found, suitable for diagnostics
+    pub message: String,
+}
+
+impl ProbeReport {
+    /// Create a report with the given status and message.
+    pub fn new(status: StoreStatus, message: &str) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    /// Whether the store can be used without any user interaction.
+    pub fn is_available(&self) -> bool {
+        self.status == StoreStatus::Available
+    }
+}
+
+impl std::fmt::Display for ProbeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.status, self.message)
+    }
+}
+
 /// The API that [credential builders](CredentialBuilder) implement.
 pub trait CredentialBuilderApi {
     /// Create a credential identified by the given target, service, and user.
@@ -147,6 +330,91 @@ pub trait CredentialBuilderApi {
     fn persistence(&self) -> CredentialPersistence {
         CredentialPersistence::UntilDelete
     }
+
+    /// Find the existing credentials in the store that match the given spec.
+    ///
+    /// The spec maps names to required values.  The names `target`, `service`,
+    /// and `user` match the values given when an entry was created; any other
+    /// names are matched against the credential's attributes (see
+    /// [get_attributes](CredentialApi::get_attributes)).  An empty spec matches
+    /// every credential in the store that the builder knows how to find.
+    ///
+    /// A default implementation is provided for backward compatibility,
+    /// since this API was added in a minor release.  The default returns a
+    /// [NotSupportedByStore](crate::Error::NotSupportedByStore) error.
+    fn search(&self, _spec: &HashMap<&str, &str>) -> Result<Vec<Box<Credential>>> {
+        Err(Error::NotSupportedByStore("search".to_string()))
+    }
+
+    /// Whether credentials produced by this builder implement
+    /// [set_secret_if](CredentialApi::set_secret_if) atomically.
+    ///
+    /// A default implementation is provided for backward compatibility,
+    /// since this API was added in a minor release.  The default
+    /// matches the default (emulated) implementation of `set_secret_if`.
+    fn atomic_updates(&self) -> bool {
+        false
+    }
+
+    /// A description of what this builder and its credentials can do.
+    ///
+    /// A default implementation is provided for backward compatibility,
+    /// since this API was added in a minor release.  The default claims
+    /// no capabilities other than those reported by other builder calls.
+    fn capabilities(&self) -> CredentialCapabilities {
+        CredentialCapabilities {
+            atomic_updates: self.atomic_updates(),
+            ..Default::default()
+        }
+    }
+
+    /// Get the secrets of several credentials made by this builder.
+    ///
+    /// The results are in the same order as the credentials, and each
+    /// is what [get_secret](CredentialApi::get_secret) would have returned.
+    /// Stores that can fetch many secrets in one request should do so.
+    ///
+    /// A default implementation is provided for backward compatibility,
+    /// since this API was added in a minor release.  The default gets
+    /// the secret of each credential in turn.
+    fn get_secrets_many(&self, credentials: &[&Credential]) -> Vec<Result<Vec<u8>>> {
+        credentials.iter().map(|c| c.get_secret()).collect()
+    }
+
+    /// Check whether the underlying store can actually be used.
+    ///
+    /// This never changes the content of the store, but it may
+    /// take a while (e.g., if the store is reached over the network).
+    ///
+    /// A default implementation is provided for backward compatibility,
+    /// since this API was added in a minor release.  The default reads a
+    /// credential with service `keyring-rs-probe` and user `probe`, and reports
+    /// the store as available if that succeeds or fails because there is
+    /// no such credential.  Other errors are mapped to the closest status.
+    fn probe(&self) -> ProbeReport {
+        let result = self
+            .build(None, "keyring-rs-probe", "probe")
+            .and_then(|credential| credential.get_secret().map(|_| ()));
+        match result {
+            Ok(())
+            | Err(Error::NoEntry)
+            | Err(Error::Ambiguous(_))
+            | Err(Error::BadEncoding(_)) => {
+                ProbeReport::new(StoreStatus::Available, "the store can be read")
+            }
+            Err(err @ Error::NoStorageAccess(_)) => {
+                ProbeReport::new(StoreStatus::Inaccessible, &err.to_string())
+            }
+            Err(err) => ProbeReport::new(StoreStatus::Unreachable, &err.to_string()),
+        }
+    }
+
+    /// Whether the underlying store can be used without any user interaction.
+    ///
+    /// This is a shorthand for checking the status returned by [probe](CredentialBuilderApi::probe).
+    fn is_available(&self) -> bool {
+        self.probe().is_available()
+    }
 }
 
 impl std::fmt::Debug for CredentialBuilder {
@@ -162,7 +430,7 @@ struct NopCredentialBuilder;
 
 impl CredentialBuilderApi for NopCredentialBuilder {
     fn build(&self, _: Option<&str>, _: &str, _: &str) -> Result<Box<Credential>> {
-        Err(super::Error::NoDefaultCredentialBuilder)
+        Err(Error::NoDefaultCredentialBuilder)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -172,6 +440,10 @@ impl CredentialBuilderApi for NopCredentialBuilder {
     fn persistence(&self) -> CredentialPersistence {
         CredentialPersistence::EntryOnly
     }
+
+    fn capabilities(&self) -> CredentialCapabilities {
+        CredentialCapabilities::default()
+    }
 }
 
 // Return a credential builder that always fails. This is the builder
@@ -57,6 +57,16 @@ pub enum Error {
     /// This indicates that there was no default credential builder to use;
     /// the client must set one before creating entries.
     NoDefaultCredentialBuilder,
+    /// This indicates that the credential store doesn't support the
+    /// requested operation.  The attached value names the operation.
+    NotSupportedByStore(String),
+    /// This indicates that a conditional update was not done because
+    /// the credential's stored secret was not the expected one
+    /// (typically because another writer changed it).
+    Conflict,
+    /// This indicates that the credential (or the store that holds it) is locked,
+    /// and the store was told not to prompt the user to unlock it.
+    Locked,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -90,6 +100,11 @@ impl std::fmt::Display for Error {
                     "No default credential builder is available; set one before creating entries"
                 )
             }
+            Error::NotSupportedByStore(op) => {
+                write!(f, "The credential store does not support {op}")
+            }
+            Error::Conflict => write!(f, "The stored secret is not the expected one"),
+            Error::Locked => write!(f, "The credential store is locked"),
         }
     }
 }
@@ -0,0 +1,950 @@
+/*!
+
+# Fake secret service
+
+When the `fake-secret-service` feature is specified, this module provides
+[FakeSecretService], a minimal in-process implementation of the
+[Secret Service API](https://specifications.freedesktop.org/secret-service-spec/latest/)
+that serves on a private D-Bus.  It lets the [secret-service](crate::secret_service)
+store, and clients that use it, be tested on any Linux box, without a desktop
+session or a running gnome-keyring.
+
+The fake starts its own `dbus-daemon` (which must be on the `PATH`), claims the
+secret service's name on that bus, and answers calls on a background thread.
+It keeps its collections and items in memory, and supports:
+
+- plain (unencrypted) sessions only, so clients must not ask for encryption
+  (if the `encrypted` feature is specified, build credentials with
+  [with_encryption](crate::secret_service::SsCredentialBuilder::with_encryption));
+- a default collection (labeled `login`), and the creation and deletion of others;
+- items with labels, attributes, and secrets, which can be found by
+  service-wide and collection-wide searches;
+- locking and unlocking of collections (an item is locked if its collection is);
+- prompts for unlocking and for creating collections, which are approved or
+  dismissed as soon as the client shows them, or left for the client to dismiss
+  (see [PromptAction]).
+
+The secret-service store always connects to the D-Bus session bus, so clients
+use a fake by pointing the `DBUS_SESSION_BUS_ADDRESS` environment variable at
+the fake's [address](FakeSecretService::address) before they first connect.
+[FakeSecretService::install] starts a fake that is shared by the whole process
+and does that for you:
+```no_run
+# use keyring::{Entry, fake_secret_service::FakeSecretService};
+# use keyring::secret_service::{EncryptionType, SsCredentialBuilder};
+// SAFETY: no other threads are running yet
+let fake = unsafe { FakeSecretService::install() }.expect("Can't start the fake");
+let builder = SsCredentialBuilder::with_encryption(EncryptionType::Plain);
+keyring::set_default_credential_builder(Box::new(builder));
+let entry = Entry::new("service", "user").unwrap();
+entry.set_password("test password").unwrap();
+fake.lock_all();
+assert_eq!(entry.get_password().unwrap(), "test password");
+assert_eq!(fake.prompts_shown(), 1);
+```
+ */
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::CString;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, mpsc};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dbus::arg::{PropMap, RefArg, TypeMismatchError, Variant};
+use dbus::blocking::Connection;
+use dbus::channel::Channel;
+use dbus::strings::{ErrorName, Path};
+use dbus::{Message, MessageType};
+use log::warn;
+
+use super::error::{Error, Result};
+
+const SERVICE_NAME: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const DEFAULT_COLLECTION_PATH: &str = "/org/freedesktop/secrets/collection/login";
+
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const COLLECTION_INTERFACE: &str = "org.freedesktop.Secret.Collection";
+const ITEM_INTERFACE: &str = "org.freedesktop.Secret.Item";
+const SESSION_INTERFACE: &str = "org.freedesktop.Secret.Session";
+const PROMPT_INTERFACE: &str = "org.freedesktop.Secret.Prompt";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+const COLLECTION_LABEL: &str = "org.freedesktop.Secret.Collection.Label";
+const ITEM_LABEL: &str = "org.freedesktop.Secret.Item.Label";
+const ITEM_ATTRIBUTES: &str = "org.freedesktop.Secret.Item.Attributes";
+
+/// Start a bus daemon that prints its address on our stdout, and that is
+/// killed when our end of its stdin is closed (even if this process dies).
+const BUS_SCRIPT: &str =
+    "dbus-daemon --session --nofork --print-address=3 3>&1 1>/dev/null & read -r _; kill $!";
+
+/// How often the server thread checks whether it has been stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The process-wide fake started by [FakeSecretService::install].
+static SHARED: Mutex<Option<&'static FakeSecretService>> = Mutex::new(None);
+
+/// How the fake answers the prompts that clients show.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PromptAction {
+    /// The user approves the prompt, so the operation is done.
+    #[default]
+    Approve,
+    /// The user dismisses the prompt, so the operation is canceled.
+    Dismiss,
+    /// The user never answers the prompt, so it stays open until the client dismisses it.
+    Ignore,
+}
+
+/// A fake secret service, serving on its own private D-Bus.
+///
+/// The service (and its bus) are stopped when this is dropped.
+/// See the module docs for details.
+#[derive(Debug)]
+pub struct FakeSecretService {
+    address: String,
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+    server: Option<JoinHandle<()>>,
+    bus: Child,
+}
+
+impl FakeSecretService {
+    /// Start a private bus and serve a fake secret service on it.
+    ///
+    /// The service starts with an empty, unlocked default collection.
+    /// Clients reach it at the returned service's [address](FakeSecretService::address).
+    pub fn start() -> Result<Self> {
+        let mut bus = Command::new("sh")
+            .args(["-c", BUS_SCRIPT])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| Error::PlatformFailure(Box::new(err)))?;
+        let mut address = String::new();
+        if let Some(stdout) = bus.stdout.take() {
+            let _ = BufReader::new(stdout).read_line(&mut address);
+        }
+        let mut service = Self {
+            address: address.trim().to_string(),
+            state: Arc::new(Mutex::new(State::new())),
+            stop: Arc::new(AtomicBool::new(false)),
+            server: None,
+            bus,
+        };
+        if service.address.is_empty() {
+            return Err(Error::PlatformFailure(
+                "couldn't start a dbus-daemon for the fake secret service".into(),
+            ));
+        }
+        let (started, result) = mpsc::channel();
+        let address = service.address.clone();
+        let state = service.state.clone();
+        let stop = service.stop.clone();
+        let server = std::thread::Builder::new()
+            .name("fake-secret-service".to_string())
+            .spawn(move || serve(&address, state, stop, started))
+            .map_err(|err| Error::PlatformFailure(Box::new(err)))?;
+        service.server = Some(server);
+        result
+            .recv()
+            .map_err(|err| Error::PlatformFailure(Box::new(err)))??;
+        Ok(service)
+    }
+
+    /// Start a fake that's shared by the whole process, and point the
+    /// `DBUS_SESSION_BUS_ADDRESS` environment variable at it.
+    ///
+    /// The fake is only started by the first call; later calls return the same one.
+    /// It's never stopped, but its bus exits when the process does.
+    ///
+    /// Since the D-Bus library only looks up the session bus address
+    /// the first time it connects to it, this must be called before anything
+    /// in the process connects to the session bus.
+    ///
+    /// # Safety
+    ///
+    /// The first call sets an environment variable, so it must not be made while
+    /// other threads might be reading the environment with non-Rust code
+    /// (such as the D-Bus library).  See [std::env::set_var].
+    pub unsafe fn install() -> Result<&'static Self> {
+        let mut shared = SHARED.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(service) = *shared {
+            return Ok(service);
+        }
+        let service: &'static Self = Box::leak(Box::new(Self::start()?));
+        // SAFETY: passed on to our caller
+        unsafe { std::env::set_var("DBUS_SESSION_BUS_ADDRESS", service.address()) };
+        *shared = Some(service);
+        Ok(service)
+    }
+
+    /// The D-Bus address of the fake's private bus.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Choose how prompts will be answered from now on.
+    pub fn set_prompt_action(&self, action: PromptAction) {
+        self.state().prompt_action = action;
+    }
+
+    /// The number of prompts that clients have shown.
+    pub fn prompts_shown(&self) -> usize {
+        self.state().prompt_windows.len()
+    }
+
+    /// The parent window ids that clients gave for the prompts they have shown, in order.
+    pub fn prompt_windows(&self) -> Vec<String> {
+        self.state().prompt_windows.clone()
+    }
+
+    /// The number of `GetSecrets` calls that clients have made.
+    pub fn get_secrets_calls(&self) -> usize {
+        self.state().get_secrets_calls
+    }
+
+    /// Lock every collection, as happens when the user's session is locked.
+    pub fn lock_all(&self) {
+        for collection in self.state().collections.values_mut() {
+            collection.locked = true;
+        }
+    }
+
+    /// Unlock every collection, without prompting.
+    pub fn unlock_all(&self) {
+        for collection in self.state().collections.values_mut() {
+            collection.locked = false;
+        }
+    }
+
+    /// Forget all the clients' sessions, as happens when the service restarts.
+    ///
+    /// Calls that use a forgotten session fail.
+    pub fn end_sessions(&self) {
+        self.state().sessions.clear();
+    }
+
+    /// The labels of all the collections.
+    pub fn collection_labels(&self) -> Vec<String> {
+        let state = self.state();
+        state
+            .collections
+            .values()
+            .map(|c| c.label.clone())
+            .collect()
+    }
+
+    /// The total number of items in all the collections.
+    pub fn item_count(&self) -> usize {
+        let state = self.state();
+        state.collections.values().map(|c| c.items.len()).sum()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Drop for FakeSecretService {
+    /// Stop serving, and stop the bus.
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+        drop(self.bus.stdin.take());
+        let _ = self.bus.wait();
+    }
+}
+
+/// Connect to the bus, claim the service name, and serve until stopped.
+///
+/// The result of connecting is sent on `started`.
+fn serve(
+    address: &str,
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+    started: mpsc::Sender<Result<()>>,
+) {
+    let connection = match connect(address) {
+        Ok(connection) => connection,
+        Err(err) => {
+            let _ = started.send(Err(err));
+            return;
+        }
+    };
+    let _ = started.send(Ok(()));
+    let channel = connection.channel();
+    while !stop.load(Ordering::Relaxed) {
+        let message = match channel.blocking_pop_message(POLL_INTERVAL) {
+            Ok(Some(message)) => message,
+            Ok(None) => continue,
+            Err(err) => {
+                warn!("fake secret service lost its bus: {err}");
+                return;
+            }
+        };
+        if message.msg_type() != MessageType::MethodCall {
+            continue;
+        }
+        let mut signals = vec![];
+        let reply = {
+            let mut state = state.lock().unwrap_or_else(|err| err.into_inner());
+            match state.handle(&message, &mut signals) {
+                Ok(reply) => reply,
+                Err(fault) => fault.reply_to(&message),
+            }
+        };
+        for message in std::iter::once(reply).chain(signals) {
+            let _ = channel.send(message);
+        }
+    }
+}
+
+fn connect(address: &str) -> Result<Connection> {
+    let failure = |err: dbus::Error| Error::PlatformFailure(Box::new(err));
+    let mut channel = Channel::open_private(address).map_err(failure)?;
+    channel.register().map_err(failure)?;
+    let connection = Connection::from(channel);
+    connection
+        .request_name(SERVICE_NAME, false, true, true)
+        .map_err(failure)?;
+    Ok(connection)
+}
+
+//
+// The service's data
+//
+
+/// A secret as it's passed over the bus: session, parameters, value, and content type.
+type Secret = (Path<'static>, Vec<u8>, Vec<u8>, String);
+
+#[derive(Debug)]
+struct State {
+    collections: BTreeMap<String, FakeCollection>,
+    aliases: HashMap<String, String>,
+    sessions: HashSet<String>,
+    prompts: HashMap<String, Pending>,
+    prompt_action: PromptAction,
+    prompt_windows: Vec<String>,
+    get_secrets_calls: usize,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+struct FakeCollection {
+    label: String,
+    locked: bool,
+    created: u64,
+    modified: u64,
+    items: BTreeMap<String, FakeItem>,
+}
+
+#[derive(Debug)]
+struct FakeItem {
+    label: String,
+    attributes: HashMap<String, String>,
+    secret: Vec<u8>,
+    content_type: String,
+    created: u64,
+    modified: u64,
+}
+
+/// The operation that a prompt will do if it's approved.
+#[derive(Debug)]
+enum Pending {
+    Unlock(Vec<Path<'static>>),
+    CreateCollection { label: String, alias: String },
+}
+
+/// A D-Bus error to send in reply to a call.
+#[derive(Debug)]
+struct Fault {
+    name: &'static str,
+    message: String,
+}
+
+impl Fault {
+    fn new(name: &'static str, message: &str) -> Self {
+        Self {
+            name,
+            message: message.to_string(),
+        }
+    }
+
+    fn unknown(path: &str, interface: &str, member: &str) -> Self {
+        Self::new(
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            &format!("there is no {interface}.{member} at {path}"),
+        )
+    }
+
+    fn locked() -> Self {
+        Self::new(
+            "org.freedesktop.Secret.Error.IsLocked",
+            "the item is locked",
+        )
+    }
+
+    fn no_session() -> Self {
+        Self::new("org.freedesktop.Secret.Error.NoSession", "no such session")
+    }
+
+    fn reply_to(&self, message: &Message) -> Message {
+        let name = ErrorName::new(self.name).expect("fault names are valid");
+        let text = CString::new(self.message.replace('\0', "")).unwrap_or_default();
+        message.error(&name, &text)
+    }
+}
+
+impl From<TypeMismatchError> for Fault {
+    fn from(err: TypeMismatchError) -> Self {
+        Self::new("org.freedesktop.DBus.Error.InvalidArgs", &err.to_string())
+    }
+}
+
+impl State {
+    fn new() -> Self {
+        let mut state = Self {
+            collections: BTreeMap::new(),
+            aliases: HashMap::new(),
+            sessions: HashSet::new(),
+            prompts: HashMap::new(),
+            prompt_action: PromptAction::default(),
+            prompt_windows: Vec::new(),
+            get_secrets_calls: 0,
+            next_id: 0,
+        };
+        state.add_collection(DEFAULT_COLLECTION_PATH.to_string(), "login", "default");
+        state
+    }
+
+    fn new_path(&mut self, parent: &str) -> String {
+        self.next_id += 1;
+        format!("{parent}/{}", self.next_id)
+    }
+
+    fn add_collection(&mut self, path: String, label: &str, alias: &str) {
+        let now = now();
+        let collection = FakeCollection {
+            label: label.to_string(),
+            locked: false,
+            created: now,
+            modified: now,
+            items: BTreeMap::new(),
+        };
+        if !alias.is_empty() {
+            self.aliases.insert(alias.to_string(), path.clone());
+        }
+        self.collections.insert(path, collection);
+    }
+
+    fn item(&self, path: &str) -> Option<(&FakeCollection, &FakeItem)> {
+        let collection = self.collections.get(parent(path))?;
+        Some((collection, collection.items.get(path)?))
+    }
+
+    /// The item at the given path, and whether it's locked.
+    fn item_mut(&mut self, path: &str) -> Option<(bool, &mut FakeItem)> {
+        let collection = self.collections.get_mut(parent(path))?;
+        Some((collection.locked, collection.items.get_mut(path)?))
+    }
+
+    /// The path of the collection that is, or holds, the given object.
+    fn owner(&self, path: &str) -> Option<String> {
+        if self.collections.contains_key(path) {
+            Some(path.to_string())
+        } else {
+            self.item(path).map(|_| parent(path).to_string())
+        }
+    }
+
+    fn check_session(&self, session: &Path) -> std::result::Result<(), Fault> {
+        if self.sessions.contains(&session.to_string()) {
+            Ok(())
+        } else {
+            Err(Fault::no_session())
+        }
+    }
+
+    fn new_prompt(&mut self, pending: Pending) -> Path<'static> {
+        let path = self.new_path(&format!("{SERVICE_PATH}/prompt"));
+        self.prompts.insert(path.clone(), pending);
+        object_path(&path)
+    }
+
+    /// Handle a method call, returning the reply and adding any signals that
+    /// should be sent after it.
+    fn handle(
+        &mut self,
+        message: &Message,
+        signals: &mut Vec<Message>,
+    ) -> std::result::Result<Message, Fault> {
+        let path = message.path().map(|p| p.to_string()).unwrap_or_default();
+        let interface = message
+            .interface()
+            .map(|i| i.to_string())
+            .unwrap_or_default();
+        let member = message.member().map(|m| m.to_string()).unwrap_or_default();
+        match interface.as_str() {
+            PROPERTIES_INTERFACE => self.handle_property(message, &path, &member),
+            SERVICE_INTERFACE if path == SERVICE_PATH => self.handle_service(message, &member),
+            COLLECTION_INTERFACE if self.collections.contains_key(&path) => {
+                self.handle_collection(message, &path, &member)
+            }
+            ITEM_INTERFACE if self.item(&path).is_some() => {
+                self.handle_item(message, &path, &member)
+            }
+            SESSION_INTERFACE if member == "Close" && self.sessions.remove(&path) => {
+                Ok(message.method_return())
+            }
+            PROMPT_INTERFACE if self.prompts.contains_key(&path) => {
+                self.handle_prompt(message, &path, &member, signals)
+            }
+            _ => Err(Fault::unknown(&path, &interface, &member)),
+        }
+    }
+
+    fn handle_service(
+        &mut self,
+        message: &Message,
+        member: &str,
+    ) -> std::result::Result<Message, Fault> {
+        let reply = message.method_return();
+        match member {
+            "OpenSession" => {
+                let (algorithm, _): (&str, Variant<Box<dyn RefArg>>) = message.read2()?;
+                if algorithm != "plain" {
+                    return Err(Fault::new(
+                        "org.freedesktop.DBus.Error.NotSupported",
+                        "only plain sessions are supported",
+                    ));
+                }
+                let path = self.new_path(&format!("{SERVICE_PATH}/session"));
+                self.sessions.insert(path.clone());
+                Ok(reply.append2(Variant(String::new()), object_path(&path)))
+            }
+            "CreateCollection" => {
+                let (properties, alias): (PropMap, &str) = message.read2()?;
+                if let Some(path) = self.aliases.get(alias) {
+                    return Ok(reply.append2(object_path(path), object_path("/")));
+                }
+                let label = properties
+                    .get(COLLECTION_LABEL)
+                    .and_then(|label| label.0.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let alias = alias.to_string();
+                let prompt = self.new_prompt(Pending::CreateCollection { label, alias });
+                Ok(reply.append2(object_path("/"), prompt))
+            }
+            "SearchItems" => {
+                let attributes: HashMap<String, String> = message.read1()?;
+                let (mut unlocked, mut locked) = (vec![], vec![]);
+                for collection in self.collections.values() {
+                    let found = collection.search(&attributes);
+                    if collection.locked {
+                        locked.extend(found);
+                    } else {
+                        unlocked.extend(found);
+                    }
+                }
+                Ok(reply.append2(unlocked, locked))
+            }
+            "Unlock" => {
+                let objects: Vec<Path<'static>> = message.read1()?;
+                let locked = objects.iter().any(|object| {
+                    self.owner(object)
+                        .is_some_and(|owner| self.collections[&owner].locked)
+                });
+                if locked {
+                    let prompt = self.new_prompt(Pending::Unlock(objects));
+                    Ok(reply.append2(Vec::<Path<'static>>::new(), prompt))
+                } else {
+                    Ok(reply.append2(objects, object_path("/")))
+                }
+            }
+            "Lock" => {
+                let objects: Vec<Path<'static>> = message.read1()?;
+                self.set_locked(&objects, true);
+                Ok(reply.append2(objects, object_path("/")))
+            }
+            "GetSecrets" => {
+                let (items, session): (Vec<Path<'static>>, Path<'static>) = message.read2()?;
+                self.check_session(&session)?;
+                self.get_secrets_calls += 1;
+                let mut secrets: HashMap<Path<'static>, Secret> = HashMap::new();
+                for path in items {
+                    if let Some((collection, item)) = self.item(&path) {
+                        if !collection.locked {
+                            secrets.insert(path.clone(), item.secret(&session));
+                        }
+                    }
+                }
+                Ok(reply.append1(secrets))
+            }
+            "ReadAlias" => {
+                let alias: &str = message.read1()?;
+                let path = self.aliases.get(alias).map(String::as_str).unwrap_or("/");
+                Ok(reply.append1(object_path(path)))
+            }
+            "SetAlias" => {
+                let (alias, path): (&str, Path<'static>) = message.read2()?;
+                if &*path == "/" {
+                    self.aliases.remove(alias);
+                } else if self.collections.contains_key(&*path) {
+                    self.aliases.insert(alias.to_string(), path.to_string());
+                } else {
+                    return Err(Fault::new(
+                        "org.freedesktop.Secret.Error.NoSuchObject",
+                        "no such collection",
+                    ));
+                }
+                Ok(reply)
+            }
+            _ => Err(Fault::unknown(SERVICE_PATH, SERVICE_INTERFACE, member)),
+        }
+    }
+
+    fn handle_collection(
+        &mut self,
+        message: &Message,
+        path: &str,
+        member: &str,
+    ) -> std::result::Result<Message, Fault> {
+        let reply = message.method_return();
+        match member {
+            "Delete" => {
+                self.collections.remove(path);
+                self.aliases.retain(|_, target| target != path);
+                Ok(reply.append1(object_path("/")))
+            }
+            "SearchItems" => {
+                let attributes: HashMap<String, String> = message.read1()?;
+                Ok(reply.append1(self.collections[path].search(&attributes)))
+            }
+            "CreateItem" => {
+                let (properties, secret, replace): (PropMap, Secret, bool) = message.read3()?;
+                self.check_session(&secret.0)?;
+                let label = properties
+                    .get(ITEM_LABEL)
+                    .and_then(|label| label.0.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let attributes = properties
+                    .get(ITEM_ATTRIBUTES)
+                    .and_then(|attributes| string_map(&*attributes.0))
+                    .unwrap_or_default();
+                let item_path = if self.collections[path].locked {
+                    return Err(Fault::locked());
+                } else if let Some(existing) = self.collections[path]
+                    .items
+                    .iter()
+                    .find(|(_, item)| replace && item.attributes == attributes)
+                {
+                    existing.0.clone()
+                } else {
+                    self.new_path(path)
+                };
+                let now = now();
+                let collection = self.collections.get_mut(path).expect("checked above");
+                let created = collection.items.get(&item_path).map_or(now, |i| i.created);
+                let item = FakeItem {
+                    label,
+                    attributes,
+                    secret: secret.2,
+                    content_type: secret.3,
+                    created,
+                    modified: now,
+                };
+                collection.items.insert(item_path.clone(), item);
+                collection.modified = now;
+                Ok(reply.append2(object_path(&item_path), object_path("/")))
+            }
+            _ => Err(Fault::unknown(path, COLLECTION_INTERFACE, member)),
+        }
+    }
+
+    fn handle_item(
+        &mut self,
+        message: &Message,
+        path: &str,
+        member: &str,
+    ) -> std::result::Result<Message, Fault> {
+        let reply = message.method_return();
+        match member {
+            "Delete" => {
+                if let Some(collection) = self.collections.get_mut(parent(path)) {
+                    collection.items.remove(path);
+                }
+                Ok(reply.append1(object_path("/")))
+            }
+            "GetSecret" => {
+                let session: Path<'static> = message.read1()?;
+                self.check_session(&session)?;
+                let (collection, item) = self.item(path).expect("checked by caller");
+                if collection.locked {
+                    return Err(Fault::locked());
+                }
+                Ok(reply.append1(item.secret(&session)))
+            }
+            "SetSecret" => {
+                let secret: Secret = message.read1()?;
+                self.check_session(&secret.0)?;
+                let (locked, item) = self.item_mut(path).expect("checked by caller");
+                if locked {
+                    return Err(Fault::locked());
+                }
+                item.secret = secret.2;
+                item.content_type = secret.3;
+                item.modified = now();
+                Ok(reply)
+            }
+            _ => Err(Fault::unknown(path, ITEM_INTERFACE, member)),
+        }
+    }
+
+    fn handle_prompt(
+        &mut self,
+        message: &Message,
+        path: &str,
+        member: &str,
+        signals: &mut Vec<Message>,
+    ) -> std::result::Result<Message, Fault> {
+        let dismissed = match member {
+            "Prompt" => {
+                let window: &str = message.read1()?;
+                self.prompt_windows.push(window.to_string());
+                match self.prompt_action {
+                    PromptAction::Ignore => return Ok(message.method_return()),
+                    action => action == PromptAction::Dismiss,
+                }
+            }
+            "Dismiss" => true,
+            _ => return Err(Fault::unknown(path, PROMPT_INTERFACE, member)),
+        };
+        let pending = self.prompts.remove(path).expect("checked by caller");
+        let result: Box<dyn RefArg> = match pending {
+            _ if dismissed => Box::new(String::new()),
+            Pending::Unlock(objects) => {
+                self.set_locked(&objects, false);
+                Box::new(objects)
+            }
+            Pending::CreateCollection { label, alias } => {
+                let collection = self.new_path(&format!("{SERVICE_PATH}/collection"));
+                self.add_collection(collection.clone(), &label, &alias);
+                Box::new(object_path(&collection))
+            }
+        };
+        let completed = Message::new_signal(path, PROMPT_INTERFACE, "Completed")
+            .expect("prompt paths are valid")
+            .append2(dismissed, Variant(result));
+        signals.push(completed);
+        Ok(message.method_return())
+    }
+
+    fn handle_property(
+        &mut self,
+        message: &Message,
+        path: &str,
+        member: &str,
+    ) -> std::result::Result<Message, Fault> {
+        let reply = message.method_return();
+        match member {
+            "Get" => {
+                let (interface, name): (&str, &str) = message.read2()?;
+                if path == SERVICE_PATH && interface == SERVICE_INTERFACE && name == "Collections" {
+                    let collections: Vec<Path<'static>> =
+                        self.collections.keys().map(|p| object_path(p)).collect();
+                    return Ok(reply.append1(Variant(collections)));
+                }
+                if let Some(collection) = self.collections.get(path) {
+                    match (interface, name) {
+                        (COLLECTION_INTERFACE, "Items") => {
+                            let items: Vec<Path<'static>> =
+                                collection.items.keys().map(|p| object_path(p)).collect();
+                            return Ok(reply.append1(Variant(items)));
+                        }
+                        (COLLECTION_INTERFACE, "Label") => {
+                            return Ok(reply.append1(Variant(collection.label.clone())));
+                        }
+                        (COLLECTION_INTERFACE, "Locked") => {
+                            return Ok(reply.append1(Variant(collection.locked)));
+                        }
+                        (COLLECTION_INTERFACE, "Created") => {
+                            return Ok(reply.append1(Variant(collection.created)));
+                        }
+                        (COLLECTION_INTERFACE, "Modified") => {
+                            return Ok(reply.append1(Variant(collection.modified)));
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some((collection, item)) = self.item(path) {
+                    match (interface, name) {
+                        (ITEM_INTERFACE, "Locked") => {
+                            return Ok(reply.append1(Variant(collection.locked)));
+                        }
+                        (ITEM_INTERFACE, "Attributes") => {
+                            return Ok(reply.append1(Variant(item.attributes.clone())));
+                        }
+                        (ITEM_INTERFACE, "Label") => {
+                            return Ok(reply.append1(Variant(item.label.clone())));
+                        }
+                        (ITEM_INTERFACE, "Type") => {
+                            return Ok(reply.append1(Variant(item.content_type.clone())));
+                        }
+                        (ITEM_INTERFACE, "Created") => {
+                            return Ok(reply.append1(Variant(item.created)));
+                        }
+                        (ITEM_INTERFACE, "Modified") => {
+                            return Ok(reply.append1(Variant(item.modified)));
+                        }
+                        _ => {}
+                    }
+                }
+                Err(Fault::unknown(path, interface, name))
+            }
+            "Set" => {
+                let (interface, name, value): (&str, &str, Variant<Box<dyn RefArg>>) =
+                    message.read3()?;
+                let value = &*value.0;
+                let now = now();
+                if let Some(collection) = self.collections.get_mut(path) {
+                    if let (COLLECTION_INTERFACE, "Label", Some(label)) =
+                        (interface, name, value.as_str())
+                    {
+                        collection.label = label.to_string();
+                        collection.modified = now;
+                        return Ok(reply);
+                    }
+                }
+                if let Some((_, item)) = self.item_mut(path) {
+                    match (interface, name) {
+                        (ITEM_INTERFACE, "Label") if value.as_str().is_some() => {
+                            item.label = value.as_str().unwrap_or_default().to_string();
+                        }
+                        (ITEM_INTERFACE, "Type") if value.as_str().is_some() => {
+                            item.content_type = value.as_str().unwrap_or_default().to_string();
+                        }
+                        (ITEM_INTERFACE, "Attributes") if string_map(value).is_some() => {
+                            item.attributes = string_map(value).unwrap_or_default();
+                        }
+                        _ => return Err(Fault::unknown(path, interface, name)),
+                    }
+                    item.modified = now;
+                    return Ok(reply);
+                }
+                Err(Fault::unknown(path, interface, name))
+            }
+            _ => Err(Fault::unknown(path, PROPERTIES_INTERFACE, member)),
+        }
+    }
+
+    /// Lock or unlock the collections that hold the given objects.
+    fn set_locked(&mut self, objects: &[Path<'static>], locked: bool) {
+        let owners: Vec<String> = objects.iter().filter_map(|o| self.owner(o)).collect();
+        for owner in owners {
+            if let Some(collection) = self.collections.get_mut(&owner) {
+                collection.locked = locked;
+            }
+        }
+    }
+}
+
+impl FakeCollection {
+    fn search(&self, attributes: &HashMap<String, String>) -> Vec<Path<'static>> {
+        self.items
+            .iter()
+            .filter(|(_, item)| {
+                attributes
+                    .iter()
+                    .all(|(key, value)| item.attributes.get(key) == Some(value))
+            })
+            .map(|(path, _)| object_path(path))
+            .collect()
+    }
+}
+
+impl FakeItem {
+    fn secret(&self, session: &Path<'static>) -> Secret {
+        let (secret, content_type) = (self.secret.clone(), self.content_type.clone());
+        (session.clone(), vec![], secret, content_type)
+    }
+}
+
+/// The path of the object that contains the object at the given path.
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn object_path(path: &str) -> Path<'static> {
+    Path::new(path.to_string()).expect("object paths are valid")
+}
+
+/// Read a D-Bus string-to-string dictionary.
+fn string_map(value: &dyn RefArg) -> Option<HashMap<String, String>> {
+    let mut entries = value.as_iter()?;
+    let mut map = HashMap::new();
+    while let Some(key) = entries.next() {
+        let value = entries.next()?;
+        map.insert(key.as_str()?.to_string(), value.as_str()?.to_string());
+    }
+    Some(map)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use dbus::arg::{RefArg, Variant};
+    use dbus::blocking::Connection;
+    use dbus::channel::Channel;
+    use dbus::strings::Path;
+
+    use super::{
+        DEFAULT_COLLECTION_PATH, FakeSecretService, SERVICE_INTERFACE, SERVICE_NAME, SERVICE_PATH,
+    };
+
+    fn connect(fake: &FakeSecretService) -> Connection {
+        let mut channel = Channel::open_private(fake.address()).expect("Can't open channel");
+        channel.register().expect("Can't register on bus");
+        Connection::from(channel)
+    }
+
+    #[test]
+    fn test_start_and_stop() {
+        let fake = FakeSecretService::start().expect("Can't start fake");
+        let other = FakeSecretService::start().expect("Can't start second fake");
+        assert_ne!(fake.address(), other.address());
+        drop(other);
+        let connection = connect(&fake);
+        let proxy = connection.with_proxy(SERVICE_NAME, SERVICE_PATH, Duration::from_secs(2));
+        let (path,): (Path,) = proxy
+            .method_call(SERVICE_INTERFACE, "ReadAlias", ("default",))
+            .expect("Can't read default alias");
+        assert_eq!(&*path, DEFAULT_COLLECTION_PATH);
+        let input = Variant(Box::new(vec![0u8; 128]) as Box<dyn RefArg>);
+        let result: Result<(Variant<Box<dyn RefArg>>, Path), _> = proxy.method_call(
+            SERVICE_INTERFACE,
+            "OpenSession",
+            ("dh-ietf1024-sha256-aes128-cbc-pkcs7", input),
+        );
+        let err = result.expect_err("Opened an encrypted session");
+        assert_eq!(err.name(), Some("org.freedesktop.DBus.Error.NotSupported"));
+        assert_eq!(fake.collection_labels(), ["login"]);
+        assert_eq!(fake.item_count(), 0);
+    }
+}
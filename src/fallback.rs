@@ -0,0 +1,187 @@
+/*!
+
+# Fallback credential store
+
+Some platform stores can be built on machines where they can't actually
+be used.  For example, on a headless Linux box or under WSL, the
+secret-service builder works fine, but every operation on its credentials
+fails because there is no D-Bus session or no default collection.
+
+A [FallbackCredentialBuilder] wraps a list of candidate credential builders.
+The first time it is used, it checks each candidate in turn to see whether
+its store is available, and from then on it delegates everything to the first
+candidate that is.  (If none of them are available, it uses the last one,
+so that clients see that store's errors.)  The choice is logged, so you can
+see which store was picked.
+
+Each candidate is checked with its [probe](CredentialBuilderApi::probe) call,
+which never writes to the store.  A store that is available, or that is
+locked (so that using it may prompt the user to unlock it), is chosen.
+
+For example, to use the secret service when it's available, and the
+mock store otherwise:
+```rust
+# use keyring::{fallback::FallbackCredentialBuilder, mock};
+let builder = FallbackCredentialBuilder::new(vec![
+    keyring::builtin_credential_builder(),
+    mock::default_credential_builder(),
+]);
+keyring::set_default_credential_builder(Box::new(builder));
+```
+
+If the `config` feature is specified, you can get the same effect by
+listing the store names, separated by commas, in the `KEYRING_BACKEND`
+environment variable (see the [config](crate::config) module).
+ */
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use log::debug;
+
+use super::Result;
+use super::credential::{
+    Credential, CredentialBuilder, CredentialBuilderApi, CredentialCapabilities,
+    CredentialPersistence, ProbeReport, StoreStatus,
+};
+
+/// A credential builder that delegates to the first of its candidates
+/// whose store is available.
+///
+/// See the module docs for details.
+#[derive(Debug)]
+pub struct FallbackCredentialBuilder {
+    candidates: Vec<Box<CredentialBuilder>>,
+    chosen: OnceLock<usize>,
+}
+
+impl FallbackCredentialBuilder {
+    /// Create a builder that will choose among the given candidates, in order.
+    ///
+    /// No candidate is checked until the builder is first used.
+    ///
+    /// # Panics
+    ///
+    /// If there are no candidates.
+    pub fn new(candidates: Vec<Box<CredentialBuilder>>) -> Self {
+        assert!(
+            !candidates.is_empty(),
+            "A fallback credential builder needs at least one candidate"
+        );
+        Self {
+            candidates,
+            chosen: OnceLock::new(),
+        }
+    }
+
+    /// The candidate builders, in the order they are checked.
+    pub fn candidates(&self) -> &[Box<CredentialBuilder>] {
+        &self.candidates
+    }
+
+    /// The index of the chosen candidate.
+    ///
+    /// This checks the candidates if they haven't been checked yet.
+    pub fn chosen_index(&self) -> usize {
+        *self.chosen.get_or_init(|| {
+            for (i, candidate) in self.candidates.iter().enumerate() {
+                let report = candidate.probe();
+                if matches!(report.status, StoreStatus::Available | StoreStatus::Locked) {
+                    debug!("fallback builder chose candidate {i} ({report}): {candidate:?}");
+                    return i;
+                }
+                debug!("fallback builder candidate {i} is not usable ({report}): {candidate:?}");
+            }
+            let last = self.candidates.len() - 1;
+            log::warn!("no credential store is available; using the last candidate");
+            last
+        })
+    }
+
+    /// The chosen candidate builder.
+    ///
+    /// This checks the candidates if they haven't been checked yet.
+    pub fn chosen(&self) -> &CredentialBuilder {
+        self.candidates[self.chosen_index()].as_ref()
+    }
+}
+
+impl CredentialBuilderApi for FallbackCredentialBuilder {
+    /// Build a credential with the chosen candidate.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        self.chosen().build(target, service, user)
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [FallbackCredentialBuilder] to find out which candidate was chosen.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// The persistence of the chosen candidate.
+    fn persistence(&self) -> CredentialPersistence {
+        self.chosen().persistence()
+    }
+
+    /// Search with the chosen candidate.
+    fn search(&self, spec: &HashMap<&str, &str>) -> Result<Vec<Box<Credential>>> {
+        self.chosen().search(spec)
+    }
+
+    /// Whether the chosen candidate does atomic updates.
+    fn atomic_updates(&self) -> bool {
+        self.chosen().atomic_updates()
+    }
+
+    /// The capabilities of the chosen candidate.
+    fn capabilities(&self) -> CredentialCapabilities {
+        self.chosen().capabilities()
+    }
+
+    /// Get secrets with the chosen candidate.
+    fn get_secrets_many(&self, credentials: &[&Credential]) -> Vec<Result<Vec<u8>>> {
+        self.chosen().get_secrets_many(credentials)
+    }
+
+    /// Probe the chosen candidate.
+    fn probe(&self) -> ProbeReport {
+        self.chosen().probe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FallbackCredentialBuilder;
+    use crate::credential::{CredentialBuilderApi, StoreStatus, nop_credential_builder};
+    use crate::mock::{self, MockCredentialBuilder};
+    use crate::{Entry, Error};
+
+    #[test]
+    fn test_falls_back() {
+        let builder = FallbackCredentialBuilder::new(vec![
+            nop_credential_builder(),
+            mock::default_credential_builder(),
+        ]);
+        assert_eq!(builder.chosen_index(), 1);
+        assert!(builder.is_available());
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        entry.set_password("fallback").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "fallback");
+        assert!(builder.capabilities().search);
+    }
+
+    #[test]
+    fn test_first_available_wins() {
+        let builder = FallbackCredentialBuilder::new(vec![
+            mock::default_credential_builder(),
+            nop_credential_builder(),
+        ]);
+        assert!(builder.chosen().as_any().is::<MockCredentialBuilder>());
+        let builder = FallbackCredentialBuilder::new(vec![nop_credential_builder()]);
+        assert_eq!(builder.chosen_index(), 0);
+        assert_eq!(builder.probe().status, StoreStatus::Unreachable);
+        assert!(matches!(
+            builder.build(None, "service", "user"),
+            Err(Error::NoDefaultCredentialBuilder)
+        ));
+    }
+}
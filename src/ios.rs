@@ -26,7 +26,9 @@ use security_framework::passwords::{
     delete_generic_password, get_generic_password, set_generic_password,
 };
 
-use super::credential::{Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi};
+use super::credential::{
+    Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi, CredentialCapabilities,
+};
 use super::error::{Error as ErrorCode, Result, decode_password};
 
 /// The representation of a generic Keychain credential.
@@ -174,6 +176,15 @@ impl CredentialBuilderApi for IosCredentialBuilder {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    /// Keychain items have binary secrets, but no attributes,
+    /// and their service and user names must be non-empty.
+    fn capabilities(&self) -> CredentialCapabilities {
+        CredentialCapabilities {
+            binary_secrets: true,
+            ..Default::default()
+        }
+    }
 }
 
 /// Map an iOS API error to a crate error with appropriate annotation
@@ -193,7 +204,7 @@ fn decode_error(err: Error) -> ErrorCode {
 mod tests {
     use super::{IosCredential, default_credential_builder};
     use crate::credential::CredentialPersistence;
-    use crate::{Entry, Error, tests::generate_random_string};
+    use crate::{Entry, Error, testing::generate_random_string};
 
     #[test]
     fn test_persistence() {
@@ -203,8 +214,15 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn test_capabilities() {
+        let capabilities = default_credential_builder().capabilities();
+        assert!(capabilities.binary_secrets);
+        assert!(!capabilities.empty_service && !capabilities.empty_user);
+    }
+
     fn entry_new(service: &str, user: &str) -> Entry {
-        crate::tests::entry_from_constructor(IosCredential::new_with_target, service, user)
+        crate::testing::entry_from_constructor(IosCredential::new_with_target, service, user)
     }
 
     #[test]
@@ -228,32 +246,32 @@ mod tests {
 
     #[test]
     fn test_missing_entry() {
-        crate::tests::test_missing_entry(entry_new);
+        crate::testing::test_missing_entry(entry_new);
     }
 
     #[test]
     fn test_empty_password() {
-        crate::tests::test_empty_password(entry_new);
+        crate::testing::test_empty_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_ascii_password() {
-        crate::tests::test_round_trip_ascii_password(entry_new);
+        crate::testing::test_round_trip_ascii_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_non_ascii_password() {
-        crate::tests::test_round_trip_non_ascii_password(entry_new);
+        crate::testing::test_round_trip_non_ascii_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_random_secret() {
-        crate::tests::test_round_trip_random_secret(entry_new);
+        crate::testing::test_round_trip_random_secret(entry_new);
     }
 
     #[test]
     fn test_update() {
-        crate::tests::test_update(entry_new);
+        crate::testing::test_update(entry_new);
     }
 
     #[test]
@@ -280,6 +298,6 @@ mod tests {
 
     #[test]
     fn test_get_update_attributes() {
-        crate::tests::test_noop_get_update_attributes(entry_new);
+        crate::testing::test_noop_get_update_attributes(entry_new);
     }
 }
@@ -83,13 +83,23 @@ default feature set:
 - `secret-service`: Provides access to the DBus-based
   [Secret Service](https://specifications.freedesktop.org/secret-service/latest/)
   storage on Linux, FreeBSD, and OpenBSD.  This keystore provides
-  support for encrypting secrets when they are transferred across the bus,
-  specify the `encrypted` feature if you want to use this support.
+  support for encrypting secrets when they are transferred across the bus;
+  specify the `encrypted` feature if you want to use this support by default
+  (it can also be chosen at runtime, see the [secret_service] module).
   By default, this keystore requires that the DBus library be
   installed on the user's machine,
   but you can avoid this requirement by specifying the `vendored` feature
   (which will cause the build to include a static build of the dbus library).
 
+There is also a non-default feature that provides an alternative API:
+
+- `async`: Provides an [AsyncEntry](async_entry::AsyncEntry) type whose
+  store operations are `async` and don't block the executor that runs them.
+  This works with any async runtime.
+- `config`: Lets the `KEYRING_BACKEND` environment variable or a TOML
+  file named by the `KEYRING_CONFIG` environment variable choose
+  the default credential store at runtime (see the [config] module).
+
 If you suppress the default feature set when building this crate, and you
 don't separately specify one of the included keystore features for your platform,
 then no keystore will be built in, and calls to [Entry::new] and [Entry::new_with_target]
@@ -99,13 +109,23 @@ will fail unless the client brings their own keystore (see next section).
 
 In addition to the keystores implemented by this crate, clients
 are free to provide their own keystores and use those.  There are
-two mechanisms provided for this:
+three mechanisms provided for this:
 
 - Clients can give their desired credential builder to the crate
   for use by the [Entry::new] and [Entry::new_with_target] calls.
   This is done by making a call to [set_default_credential_builder].
   The major advantage of this approach is that client code remains
   independent of the credential builder being used.
+  To use the first of several stores that is available at runtime,
+  give the default a [FallbackCredentialBuilder](fallback::FallbackCredentialBuilder).
+  For tests, [with_credential_builder] (or [scoped_credential_builder])
+  overrides the default builder on just the current thread, so tests that
+  run in parallel can each use their own builder.
+
+- Clients can register any number of credential builders by name
+  with [register_credential_builder], and then pick a store by name
+  using [Entry::new_in] and [Entry::new_with_target_in].  This is
+  useful for clients who keep different secrets in different stores.
 
 - Clients can construct their concrete credentials directly and
   then turn them into entries by using the [Entry::new_with_credential]
@@ -113,6 +133,10 @@ two mechanisms provided for this:
   can be identified however clients want, rather than being restricted
   to the simple model used by this crate.
 
+If you write your own credential store, you can check that it behaves
+like the crate-provided ones by running the tests in the [testing] module
+against it (specify the `testing` feature).
+
 ## Mock Credential Store
 
 In addition to the platform-specific credential stores, this crate
@@ -125,6 +149,10 @@ credential store as your default in tests, make this call:
 keyring::set_default_credential_builder(keyring::mock::default_credential_builder())
 ```
 
+To test against the behavior of a real credential store on machines
+that don't have one, record your interactions with the real store once and
+replay them later (see the [replay] module).
+
 ## Interoperability with Third Parties
 
 Each of the platform-specific credential stores provided by this crate uses
@@ -140,6 +168,17 @@ and are documented in the headers of those modules.
 you may need to use the Platform drop-down on [docs.rs](https://docs.rs/keyring) to
 view the storage module documentation for your desired platform.)
 
+## Zeroizing secrets
+
+The passwords and secrets returned by [get_password](Entry::get_password)
+and [get_secret](Entry::get_secret) are ordinary values that leave copies
+of their content in memory after they are dropped.  If that's a concern,
+use [get_zeroizing_password](Entry::get_zeroizing_password) and
+[get_zeroizing_secret](Entry::get_zeroizing_secret) instead: these return
+values whose memory is zeroed when they are dropped (and, if you specify
+the `mlock` feature, is locked against swapping).  See the [secret] module
+for details.
+
 ## Caveats
 
 This module expects passwords to be UTF-8 encoded strings,
@@ -156,9 +195,9 @@ stores may not handle access from different threads reliably.
 In particular, accessing the same credential
 from multiple threads at the same time can fail, especially on
 Windows and Linux, because the accesses may not be serialized in the same order
-they are made. And for RPC-based credential stores such as the dbus-based Secret
-Service, accesses from multiple threads (and even the same thread very quickly)
-are not recommended, as they may cause the RPC mechanism to fail.
+they are made. (The dbus-based Secret Service store is an exception:
+it sends every access, from every thread, through a single background thread
+that does them in the order they are made.)
  */
 
 use log::debug;
@@ -166,6 +205,7 @@ use std::collections::HashMap;
 
 pub use credential::{Credential, CredentialBuilder};
 pub use error::{Error, Result};
+pub use secret::{SecretBytes, SecretString};
 
 pub mod mock;
 
@@ -182,6 +222,13 @@ pub mod mock;
 )]
 pub mod secret_service;
 
+#[cfg(all(
+    any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"),
+    feature = "fake-secret-service"
+))]
+#[cfg_attr(docsrs, doc(cfg(feature = "fake-secret-service")))]
+pub mod fake_secret_service;
+
 //
 // pick the Apple keystore
 //
@@ -202,6 +249,21 @@ pub mod windows;
 
 pub mod credential;
 pub mod error;
+pub mod fallback;
+pub mod replay;
+pub mod secret;
+
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub mod async_entry;
+
+#[cfg(feature = "config")]
+#[cfg_attr(docsrs, doc(cfg(feature = "config")))]
+pub mod config;
+
+#[cfg(any(test, feature = "testing"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub mod testing;
 
 #[derive(Default, Debug)]
 struct EntryBuilder {
@@ -228,7 +290,78 @@ pub fn set_default_credential_builder(new: Box<CredentialBuilder>) {
     guard.inner = Some(new);
 }
 
+/// The credential builders registered by name, see [register_credential_builder].
+static NAMED_BUILDERS: std::sync::LazyLock<
+    std::sync::RwLock<HashMap<String, Box<CredentialBuilder>>>,
+> = std::sync::LazyLock::new(Default::default);
+
+/// Register a credential builder under the given name.
+///
+/// Entries can then be created in the named store with
+/// [new_in](Entry::new_in) and [new_with_target_in](Entry::new_with_target_in).
+/// This allows clients who keep different secrets in different credential stores
+/// to pick a store by name, rather than building credentials themselves and
+/// using [new_with_credential](Entry::new_with_credential).
+///
+/// If a builder was already registered under the name, it is replaced.
+/// Registered builders are independent of the default credential builder.
+pub fn register_credential_builder(name: &str, builder: Box<CredentialBuilder>) {
+    debug!("registering credential builder {builder:?} as {name}");
+    let mut guard = NAMED_BUILDERS
+        .write()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    guard.insert(name.to_string(), builder);
+}
+
+/// Remove the credential builder registered under the given name, if any.
+///
+/// Returns whether there was a builder registered under that name.
+/// Entries already created in the named store are not affected.
+pub fn unregister_credential_builder(name: &str) -> bool {
+    let mut guard = NAMED_BUILDERS
+        .write()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    guard.remove(name).is_some()
+}
+
+fn with_named_builder<T>(name: &str, f: impl FnOnce(&CredentialBuilder) -> Result<T>) -> Result<T> {
+    let guard = NAMED_BUILDERS
+        .read()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    match guard.get(name) {
+        Some(builder) => f(builder.as_ref()),
+        None => Err(Error::Invalid(
+            "store".to_string(),
+            format!("no credential builder is registered as '{name}'"),
+        )),
+    }
+}
+
+/// Return the credential builder used by default to create entries,
+/// unless a client has set a different one.
+///
+/// If the `config` feature is specified, the environment can choose
+/// this builder (see the [config] module).  Otherwise, this is the
+/// [builtin_credential_builder].
 pub fn default_credential_builder() -> Box<CredentialBuilder> {
+    #[cfg(feature = "config")]
+    match config::configured_credential_builder() {
+        Ok(Some(builder)) => {
+            debug!("using configured credential builder {builder:?}");
+            return builder;
+        }
+        Ok(None) => {}
+        Err(err) => log::warn!("ignoring invalid keyring configuration: {err}"),
+    }
+    builtin_credential_builder()
+}
+
+/// Return the credential builder for the platform's built-in credential store.
+///
+/// This is the builder for the keystore selected by the feature set
+/// for the platform being built, or a builder that always fails
+/// if there is no such keystore.
+pub fn builtin_credential_builder() -> Box<CredentialBuilder> {
     #[cfg(any(
         all(target_os = "linux", feature = "secret-service"),
         all(target_os = "freebsd", feature = "secret-service"),
@@ -252,7 +385,71 @@ pub fn default_credential_builder() -> Box<CredentialBuilder> {
     credential::nop_credential_builder()
 }
 
-fn build_default_credential(target: Option<&str>, service: &str, user: &str) -> Result<Entry> {
+thread_local! {
+    /// The stack of scoped credential builders for this thread, innermost last.
+    static SCOPED_BUILDERS: std::cell::RefCell<Vec<(u64, std::rc::Rc<Box<CredentialBuilder>>)>> =
+        const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// A guard that overrides the default credential builder on the current thread
+/// until it is dropped.
+///
+/// Returned by [scoped_credential_builder].  Guards can be nested;
+/// the most recently created guard that is still alive wins.
+#[derive(Debug)]
+#[must_use = "the override ends as soon as the guard is dropped"]
+pub struct ScopedCredentialBuilder {
+    id: u64,
+    // the override is per-thread, so the guard must stay on its thread
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl Drop for ScopedCredentialBuilder {
+    fn drop(&mut self) {
+        SCOPED_BUILDERS.with_borrow_mut(|stack| stack.retain(|(id, _)| *id != self.id));
+    }
+}
+
+/// Override the credential builder used by default to create entries,
+/// but only on the current thread and only while the returned guard is alive.
+///
+/// This is meant for tests: each test can use its own (e.g., mock) builder
+/// without affecting other tests running in parallel, and without needing
+/// to call [set_default_credential_builder].  While the guard is alive,
+/// [Entry::new], [Entry::new_with_target], [Entry::search], and
+/// [default_credential_capabilities] use the given builder on this thread;
+/// other threads are unaffected.  When the guard is dropped, the
+/// previous builder is used again.
+///
+/// Because the override is per-thread, async code should only rely on it
+/// between `await` points, since a task may move between threads when it
+/// is suspended.
+pub fn scoped_credential_builder(builder: Box<CredentialBuilder>) -> ScopedCredentialBuilder {
+    static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    debug!("overriding default credential builder on this thread with {builder:?}");
+    SCOPED_BUILDERS.with_borrow_mut(|stack| stack.push((id, std::rc::Rc::new(builder))));
+    ScopedCredentialBuilder {
+        id,
+        _not_send: std::marker::PhantomData,
+    }
+}
+
+/// Run the given function with the credential builder used by default
+/// to create entries overridden on the current thread.
+///
+/// See [scoped_credential_builder] for details.
+pub fn with_credential_builder<T>(builder: Box<CredentialBuilder>, f: impl FnOnce() -> T) -> T {
+    let _guard = scoped_credential_builder(builder);
+    f()
+}
+
+fn with_default_builder<T>(f: impl FnOnce(&CredentialBuilder) -> Result<T>) -> Result<T> {
+    // clone the scoped builder out of the stack, so f can create scopes of its own
+    let scoped = SCOPED_BUILDERS.with_borrow(|stack| stack.last().map(|(_, b)| b.clone()));
+    if let Some(builder) = scoped {
+        return f(builder.as_ref().as_ref());
+    }
     static DEFAULT: std::sync::OnceLock<Box<CredentialBuilder>> = std::sync::OnceLock::new();
     let guard = DEFAULT_BUILDER
         .read()
@@ -261,7 +458,28 @@ fn build_default_credential(target: Option<&str>, service: &str, user: &str) ->
         .inner
         .as_ref()
         .unwrap_or_else(|| DEFAULT.get_or_init(|| default_credential_builder()));
-    let credential = builder.build(target, service, user)?;
+    f(builder.as_ref())
+}
+
+/// Describe the capabilities of the credential builder used by default to create entries.
+///
+/// Use this to adapt at runtime to the store that [Entry::new] will use.
+/// See [CredentialCapabilities](credential::CredentialCapabilities) for details.
+pub fn default_credential_capabilities() -> credential::CredentialCapabilities {
+    with_default_builder(|builder| Ok(builder.capabilities()))
+        .expect("Getting capabilities can't fail")
+}
+
+/// Check whether the credential store used by default to create entries can actually be used.
+///
+/// Use this for startup diagnostics, rather than writing a throwaway
+/// credential.  See [probe](credential::CredentialBuilderApi::probe) for details.
+pub fn probe_default_store() -> credential::ProbeReport {
+    with_default_builder(|builder| Ok(builder.probe())).expect("Probing can't fail")
+}
+
+fn build_default_credential(target: Option<&str>, service: &str, user: &str) -> Result<Entry> {
+    let credential = with_default_builder(|builder| builder.build(target, service, user))?;
     Ok(Entry { inner: credential })
 }
 
@@ -302,12 +520,82 @@ impl Entry {
         Ok(entry)
     }
 
+    /// Create an entry for the given service and user in the named credential store.
+    ///
+    /// The credential builder registered under the store name
+    /// (see [register_credential_builder]) is used.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Invalid](Error::Invalid) error if no builder is registered
+    /// under the store name, as well as any error returned by that builder.
+    pub fn new_in(store: &str, service: &str, user: &str) -> Result<Entry> {
+        debug!(
+            "creating entry in store {store} with service {service}, user {user}, and no target"
+        );
+        let credential = with_named_builder(store, |builder| builder.build(None, service, user))?;
+        debug!("created entry {credential:?}");
+        Ok(Entry { inner: credential })
+    }
+
+    /// Create an entry for the given target, service, and user in the named credential store.
+    ///
+    /// See [new_in](Entry::new_in) for details.
+    pub fn new_with_target_in(
+        store: &str,
+        target: &str,
+        service: &str,
+        user: &str,
+    ) -> Result<Entry> {
+        debug!(
+            "creating entry in store {store} with service {service}, user {user}, and target {target}"
+        );
+        let credential =
+            with_named_builder(store, |builder| builder.build(Some(target), service, user))?;
+        debug!("created entry {credential:?}");
+        Ok(Entry { inner: credential })
+    }
+
     /// Create an entry from a credential that may be in any credential store.
     pub fn new_with_credential(credential: Box<Credential>) -> Entry {
         debug!("create entry from {credential:?}");
         Entry { inner: credential }
     }
 
+    /// Find entries for all the existing credentials that match the given spec.
+    ///
+    /// The default credential builder is used.  The spec maps names to required
+    /// values: `target`, `service`, and `user` match the values given when
+    /// an entry was created, and any other name is matched against the
+    /// credential's attributes.  See the documentation for each credential store
+    /// for details of how it interprets the spec.
+    ///
+    /// Returns a [NotSupportedByStore](Error::NotSupportedByStore) error if the
+    /// credential store can't search for credentials.
+    pub fn search(spec: &HashMap<&str, &str>) -> Result<Vec<Entry>> {
+        debug!("searching for entries matching {spec:?}");
+        let credentials = with_default_builder(|builder| builder.search(spec))?;
+        debug!("found {} matching entries", credentials.len());
+        Ok(credentials
+            .into_iter()
+            .map(Entry::new_with_credential)
+            .collect())
+    }
+
+    /// Find entries for all the existing credentials in the named store that match the given spec.
+    ///
+    /// See [search](Entry::search) for details of the spec
+    /// and [new_in](Entry::new_in) for details of store names.
+    pub fn search_in(store: &str, spec: &HashMap<&str, &str>) -> Result<Vec<Entry>> {
+        debug!("searching store {store} for entries matching {spec:?}");
+        let credentials = with_named_builder(store, |builder| builder.search(spec))?;
+        debug!("found {} matching entries", credentials.len());
+        Ok(credentials
+            .into_iter()
+            .map(Entry::new_with_credential)
+            .collect())
+    }
+
     /// Set the password for this entry.
     ///
     /// Can return an [Ambiguous](Error::Ambiguous) error
@@ -360,6 +648,121 @@ impl Entry {
         self.inner.get_secret()
     }
 
+    /// Retrieve the password saved for this entry, in a buffer that is zeroed on drop.
+    ///
+    /// This is just like [get_password](Entry::get_password), except
+    /// that the password is returned in a [SecretString], which makes sure
+    /// that no copy of the password is left behind in memory.
+    pub fn get_zeroizing_password(&self) -> Result<SecretString> {
+        debug!("get zeroizing password from entry {:?}", self.inner);
+        self.inner.get_zeroizing_password()
+    }
+
+    /// Retrieve the secret saved for this entry, in a buffer that is zeroed on drop.
+    ///
+    /// This is just like [get_secret](Entry::get_secret), except
+    /// that the secret is returned in a [SecretBytes], which makes sure
+    /// that no copy of the secret is left behind in memory.
+    pub fn get_zeroizing_secret(&self) -> Result<SecretBytes> {
+        debug!("get zeroizing secret from entry {:?}", self.inner);
+        self.inner.get_zeroizing_secret()
+    }
+
+    /// Retrieve the secrets saved for several entries at once.
+    ///
+    /// The results are in the same order as the entries, and each is what
+    /// [get_secret](Entry::get_secret) would have returned for its entry.
+    /// Entries whose credentials have a
+    /// [batch builder](credential::CredentialApi::batch_builder) are fetched
+    /// together, with one call to its `get_secrets_many` for each type of builder;
+    /// other entries get each secret in turn.
+    pub fn get_secrets_many(entries: &[&Entry]) -> Vec<Result<Vec<u8>>> {
+        debug!("get secrets from {} entries", entries.len());
+        let mut groups: Vec<(Box<CredentialBuilder>, Vec<usize>)> = vec![];
+        let mut results: Vec<Option<Result<Vec<u8>>>> = entries.iter().map(|_| None).collect();
+        for (i, entry) in entries.iter().enumerate() {
+            let Some(builder) = entry.inner.batch_builder() else {
+                results[i] = Some(entry.inner.get_secret());
+                continue;
+            };
+            let kind = std::any::Any::type_id(builder.as_any());
+            match groups
+                .iter_mut()
+                .find(|(b, _)| std::any::Any::type_id(b.as_any()) == kind)
+            {
+                Some((_, indices)) => indices.push(i),
+                None => groups.push((builder, vec![i])),
+            }
+        }
+        for (builder, indices) in groups {
+            let credentials: Vec<&Credential> =
+                indices.iter().map(|&i| entries[i].inner.as_ref()).collect();
+            for (i, result) in indices
+                .into_iter()
+                .zip(builder.get_secrets_many(&credentials))
+            {
+                results[i] = Some(result);
+            }
+        }
+        results
+            .into_iter()
+            .zip(entries)
+            .map(|(result, entry)| result.unwrap_or_else(|| entry.inner.get_secret()))
+            .collect()
+    }
+
+    /// Set the secret for this entry, but only if its current secret is the expected one.
+    ///
+    /// An `expected` value of `None` means that there must be no credential
+    /// for this entry.  If the stored secret is not the expected one, a
+    /// [Conflict](Error::Conflict) error is returned and the secret
+    /// is not changed.
+    ///
+    /// Not all credential stores can do this atomically; those that can't
+    /// emulate it by reading and then writing the secret.  Use
+    /// [atomic_updates](credential::CredentialBuilderApi::atomic_updates)
+    /// on the store's builder to find out which kind you have.
+    ///
+    /// Can return an [Ambiguous](Error::Ambiguous) error
+    /// if there is more than one platform credential
+    /// that matches this entry.  This can only happen
+    /// on some platforms, and then only if a third-party
+    /// application wrote the ambiguous credential.
+    pub fn set_secret_if(&self, expected: Option<&[u8]>, new: &[u8]) -> Result<()> {
+        debug!("conditionally set secret for entry {:?}", self.inner);
+        self.inner.set_secret_if(expected, new)
+    }
+
+    /// Update the secret for this entry by applying a function to its current secret.
+    ///
+    /// The function is given the current secret (or `None` if there isn't one)
+    /// and returns the new secret, which is then set with
+    /// [set_secret_if](Entry::set_secret_if).  If the stored secret changes
+    /// between the read and the set, the function is applied again to the changed
+    /// secret; if that keeps happening, a [Conflict](Error::Conflict) error
+    /// is returned after a few tries.  So the function may be called more than once.
+    pub fn update_secret<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(Option<&[u8]>) -> Vec<u8>,
+    {
+        const MAX_TRIES: usize = 5;
+        debug!("update secret for entry {:?}", self.inner);
+        for _ in 0..MAX_TRIES {
+            let current = match self.inner.get_zeroizing_secret() {
+                Ok(current) => Some(current),
+                Err(Error::NoEntry) => None,
+                Err(err) => return Err(err),
+            };
+            let expected = current.as_ref().map(|c| c.expose_secret());
+            let new = SecretBytes::new(f(expected));
+            match self.inner.set_secret_if(expected, new.expose_secret()) {
+                Err(Error::Conflict) => debug!("secret changed during update; retrying"),
+                result => return result,
+            }
+        }
+        Err(Error::Conflict)
+    }
+
     /// Get the attributes on the underlying credential for this entry.
     ///
     /// Some of the underlying credential stores allow credentials to have named attributes
@@ -419,6 +822,35 @@ impl Entry {
         self.inner.delete_credential()
     }
 
+    /// Whether the storage that holds this entry's credential is locked.
+    ///
+    /// Using a locked credential needs its storage to be unlocked, which
+    /// may prompt the user.  Stores that can't be locked are never locked.
+    /// In some stores the lock covers other credentials as well;
+    /// see the documentation for each credential store for details.
+    pub fn is_locked(&self) -> Result<bool> {
+        debug!("check whether entry {:?} is locked", self.inner);
+        self.inner.is_locked()
+    }
+
+    /// Unlock the storage that holds this entry's credential, prompting the user if necessary.
+    ///
+    /// This does nothing in stores that can't be locked.
+    pub fn unlock(&self) -> Result<()> {
+        debug!("unlock entry {:?}", self.inner);
+        self.inner.unlock()
+    }
+
+    /// Lock the storage that holds this entry's credential.
+    ///
+    /// In some stores this locks other credentials as well.  Returns a
+    /// [NotSupportedByStore](Error::NotSupportedByStore) error if the store
+    /// can't be locked.
+    pub fn lock(&self) -> Result<()> {
+        debug!("lock entry {:?}", self.inner);
+        self.inner.lock()
+    }
+
     /// Return a reference to this entry's wrapped credential.
     ///
     /// The reference is of the [Any](std::any::Any) type, so it can be
@@ -431,235 +863,3 @@ impl Entry {
 
 #[cfg(doctest)]
 doc_comment::doctest!("../README.md", readme);
-
-#[cfg(test)]
-/// There are no actual tests in this module.
-/// Instead, it contains generics that each keystore invokes in their tests,
-/// passing their store-specific parameters for the generic ones.
-//
-// Since iOS doesn't use any of these generics, we allow dead code.
-#[allow(dead_code)]
-mod tests {
-    use super::{Entry, Error, Result, credential::CredentialApi};
-    use std::collections::HashMap;
-
-    /// Create a platform-specific credential given the constructor, service, and user
-    pub fn entry_from_constructor<F, T>(f: F, service: &str, user: &str) -> Entry
-    where
-        F: FnOnce(Option<&str>, &str, &str) -> Result<T>,
-        T: 'static + CredentialApi + Send + Sync,
-    {
-        match f(None, service, user) {
-            Ok(credential) => Entry::new_with_credential(Box::new(credential)),
-            Err(err) => {
-                panic!("Couldn't create entry (service: {service}, user: {user}): {err:?}")
-            }
-        }
-    }
-
-    /// Create a platform-specific credential given the constructor, service, user, and attributes
-    pub fn entry_from_constructor_and_attributes<F, T>(
-        f: F,
-        service: &str,
-        user: &str,
-        attrs: &HashMap<&str, &str>,
-    ) -> Entry
-    where
-        F: FnOnce(Option<&str>, &str, &str, &HashMap<&str, &str>) -> Result<T>,
-        T: 'static + CredentialApi + Send + Sync,
-    {
-        match f(None, service, user, attrs) {
-            Ok(credential) => Entry::new_with_credential(Box::new(credential)),
-            Err(err) => {
-                panic!("Couldn't create entry (service: {service}, user: {user}): {err:?}")
-            }
-        }
-    }
-
-    fn test_round_trip_no_delete(case: &str, entry: &Entry, in_pass: &str) {
-        entry
-            .set_password(in_pass)
-            .unwrap_or_else(|err| panic!("Can't set password for {case}: {err:?}"));
-        let out_pass = entry
-            .get_password()
-            .unwrap_or_else(|err| panic!("Can't get password for {case}: {err:?}"));
-        assert_eq!(
-            in_pass, out_pass,
-            "Passwords don't match for {case}: set='{in_pass}', get='{out_pass}'",
-        )
-    }
-
-    /// A basic round-trip unit test given an entry and a password.
-    pub fn test_round_trip(case: &str, entry: &Entry, in_pass: &str) {
-        test_round_trip_no_delete(case, entry, in_pass);
-        entry
-            .delete_credential()
-            .unwrap_or_else(|err| panic!("Can't delete password for {case}: {err:?}"));
-        let password = entry.get_password();
-        assert!(
-            matches!(password, Err(Error::NoEntry)),
-            "Read deleted password for {case}",
-        );
-    }
-
-    /// A basic round-trip unit test given an entry and a password.
-    pub fn test_round_trip_secret(case: &str, entry: &Entry, in_secret: &[u8]) {
-        entry
-            .set_secret(in_secret)
-            .unwrap_or_else(|err| panic!("Can't set secret for {case}: {err:?}"));
-        let out_secret = entry
-            .get_secret()
-            .unwrap_or_else(|err| panic!("Can't get secret for {case}: {err:?}"));
-        assert_eq!(
-            in_secret, &out_secret,
-            "Passwords don't match for {case}: set='{in_secret:?}', get='{out_secret:?}'",
-        );
-        entry
-            .delete_credential()
-            .unwrap_or_else(|err| panic!("Can't delete password for {case}: {err:?}"));
-        let password = entry.get_secret();
-        assert!(
-            matches!(password, Err(Error::NoEntry)),
-            "Read deleted password for {case}",
-        );
-    }
-
-    /// When tests fail, they leave keys behind, and those keys
-    /// have to be cleaned up before the tests can be run again
-    /// in order to avoid bad results.  So it's a lot easier just
-    /// to have tests use a random string for key names to avoid
-    /// the conflicts, and then do any needed cleanup once everything
-    /// is working correctly.  So we export this function for tests to use.
-    pub fn generate_random_string_of_len(len: usize) -> String {
-        use fastrand;
-        use std::iter::repeat_with;
-        repeat_with(fastrand::alphanumeric).take(len).collect()
-    }
-
-    pub fn generate_random_string() -> String {
-        generate_random_string_of_len(30)
-    }
-
-    fn generate_random_bytes_of_len(len: usize) -> Vec<u8> {
-        use fastrand;
-        use std::iter::repeat_with;
-        repeat_with(|| fastrand::u8(..)).take(len).collect()
-    }
-
-    pub fn test_empty_service_and_user<F>(f: F)
-    where
-        F: Fn(&str, &str) -> Entry,
-    {
-        let name = generate_random_string();
-        let in_pass = "doesn't matter";
-        test_round_trip("empty user", &f(&name, ""), in_pass);
-        test_round_trip("empty service", &f("", &name), in_pass);
-        test_round_trip("empty service & user", &f("", ""), in_pass);
-    }
-
-    pub fn test_missing_entry<F>(f: F)
-    where
-        F: FnOnce(&str, &str) -> Entry,
-    {
-        let name = generate_random_string();
-        let entry = f(&name, &name);
-        assert!(
-            matches!(entry.get_password(), Err(Error::NoEntry)),
-            "Missing entry has password"
-        )
-    }
-
-    pub fn test_empty_password<F>(f: F)
-    where
-        F: FnOnce(&str, &str) -> Entry,
-    {
-        let name = generate_random_string();
-        let entry = f(&name, &name);
-        test_round_trip("empty password", &entry, "");
-    }
-
-    pub fn test_round_trip_ascii_password<F>(f: F)
-    where
-        F: FnOnce(&str, &str) -> Entry,
-    {
-        let name = generate_random_string();
-        let entry = f(&name, &name);
-        test_round_trip("ascii password", &entry, "test ascii password");
-    }
-
-    pub fn test_round_trip_non_ascii_password<F>(f: F)
-    where
-        F: FnOnce(&str, &str) -> Entry,
-    {
-        let name = generate_random_string();
-        let entry = f(&name, &name);
-        test_round_trip("non-ascii password", &entry, "このきれいな花は桜です");
-    }
-
-    pub fn test_round_trip_random_secret<F>(f: F)
-    where
-        F: FnOnce(&str, &str) -> Entry,
-    {
-        let name = generate_random_string();
-        let entry = f(&name, &name);
-        let secret = generate_random_bytes_of_len(24);
-        test_round_trip_secret("non-ascii password", &entry, secret.as_slice());
-    }
-
-    pub fn test_update<F>(f: F)
-    where
-        F: FnOnce(&str, &str) -> Entry,
-    {
-        let name = generate_random_string();
-        let entry = f(&name, &name);
-        test_round_trip_no_delete("initial ascii password", &entry, "test ascii password");
-        test_round_trip(
-            "updated non-ascii password",
-            &entry,
-            "このきれいな花は桜です",
-        );
-    }
-
-    pub fn test_noop_get_update_attributes<F>(f: F)
-    where
-        F: FnOnce(&str, &str) -> Entry,
-    {
-        let name = generate_random_string();
-        let entry = f(&name, &name);
-        assert!(
-            matches!(entry.get_attributes(), Err(Error::NoEntry)),
-            "Read missing credential in attribute test",
-        );
-        let mut map: HashMap<&str, &str> = HashMap::new();
-        map.insert("test attribute name", "test attribute value");
-        assert!(
-            matches!(entry.update_attributes(&map), Err(Error::NoEntry)),
-            "Updated missing credential in attribute test",
-        );
-        // create the credential and test again
-        entry
-            .set_password("test password for attributes")
-            .unwrap_or_else(|err| panic!("Can't set password for attribute test: {err:?}"));
-        match entry.get_attributes() {
-            Err(err) => panic!("Couldn't get attributes: {err:?}"),
-            Ok(attrs) if attrs.is_empty() => {}
-            Ok(attrs) => panic!("Unexpected attributes: {attrs:?}"),
-        }
-        assert!(
-            matches!(entry.update_attributes(&map), Ok(())),
-            "Couldn't update attributes in attribute test",
-        );
-        match entry.get_attributes() {
-            Err(err) => panic!("Couldn't get attributes after update: {err:?}"),
-            Ok(attrs) if attrs.is_empty() => {}
-            Ok(attrs) => panic!("Unexpected attributes after update: {attrs:?}"),
-        }
-        entry
-            .delete_credential()
-            .unwrap_or_else(|err| panic!("Can't delete credential for attribute test: {err:?}"));
-        assert!(
-            matches!(entry.get_attributes(), Err(Error::NoEntry)),
-            "Read deleted credential in attribute test",
-        );
-    }
-}
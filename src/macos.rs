@@ -34,7 +34,9 @@ Credentials on macOS can have a large number of _key/value_ attributes,
 but this module controls the _account_ and _name_ attributes and
 ignores all the others. so clients can't use it to access or update any attributes.
  */
-use super::credential::{Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi};
+use super::credential::{
+    Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi, CredentialCapabilities,
+};
 use super::error::{Error as ErrorCode, Result, decode_password};
 use crate::ios::IosCredential;
 use security_framework::base::Error;
@@ -216,6 +218,15 @@ impl CredentialBuilderApi for MacCredentialBuilder {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    /// Keychain items have binary secrets, but no attributes,
+    /// and their service and user names must be non-empty.
+    fn capabilities(&self) -> CredentialCapabilities {
+        CredentialCapabilities {
+            binary_secrets: true,
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -296,7 +307,7 @@ pub fn decode_error(err: Error) -> ErrorCode {
 #[cfg(test)]
 mod tests {
     use crate::credential::CredentialPersistence;
-    use crate::{Entry, Error, tests::generate_random_string};
+    use crate::{Entry, Error, testing::generate_random_string};
 
     use super::{MacCredential, default_credential_builder};
 
@@ -308,8 +319,15 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn test_capabilities() {
+        let capabilities = default_credential_builder().capabilities();
+        assert!(capabilities.binary_secrets);
+        assert!(!capabilities.empty_service && !capabilities.empty_user);
+    }
+
     fn entry_new(service: &str, user: &str) -> Entry {
-        crate::tests::entry_from_constructor(
+        crate::testing::entry_from_constructor(
             |_, s, u| MacCredential::new_with_target(None, s, u),
             service,
             user,
@@ -332,32 +350,32 @@ mod tests {
 
     #[test]
     fn test_missing_entry() {
-        crate::tests::test_missing_entry(entry_new);
+        crate::testing::test_missing_entry(entry_new);
     }
 
     #[test]
     fn test_empty_password() {
-        crate::tests::test_empty_password(entry_new);
+        crate::testing::test_empty_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_ascii_password() {
-        crate::tests::test_round_trip_ascii_password(entry_new);
+        crate::testing::test_round_trip_ascii_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_non_ascii_password() {
-        crate::tests::test_round_trip_non_ascii_password(entry_new);
+        crate::testing::test_round_trip_non_ascii_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_random_secret() {
-        crate::tests::test_round_trip_random_secret(entry_new);
+        crate::testing::test_round_trip_random_secret(entry_new);
     }
 
     #[test]
     fn test_update() {
-        crate::tests::test_update(entry_new);
+        crate::testing::test_update(entry_new);
     }
 
     #[test]
@@ -384,7 +402,7 @@ mod tests {
 
     #[test]
     fn test_get_update_attributes() {
-        crate::tests::test_noop_get_update_attributes(entry_new);
+        crate::testing::test_noop_get_update_attributes(entry_new);
     }
 
     #[test]
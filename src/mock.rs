@@ -5,7 +5,10 @@
 To facilitate testing of clients, this crate provides a Mock credential store
 that is platform-independent, provides no persistence, and allows the client
 to specify the return values (including errors) for each call. The credentials
-in this store have no attributes at all.
+in this store have attributes that behave like those of the secret-service store:
+each credential has an `application` attribute and a `label` when it's created,
+the `target`, `service`, and `username` attributes can't be updated,
+and the label can't be set to an empty string.
 
 To use this credential store instead of the default, make this call during
 application startup _before_ creating any entries:
@@ -31,44 +34,368 @@ let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
 mock.set_error(Error::Invalid("mock error".to_string(), "takes precedence".to_string()));
 entry.set_password("test").expect_err("error will override");
 entry.set_password("test").expect("error has been cleared");
+```
+
+For more elaborate failure testing, such as testing retry logic,
+you can [script](MockCredential::script_errors) a sequence of errors for
+each kind of operation, make operations fail at random with a reproducible
+[MockChaos] generator, and make operations slow with
+[set_latency](MockCredential::set_latency).
+
+Mock credential builders remember the credentials they have built,
+so they support [search](crate::Entry::search): a search returns entries
+for every credential built by the builder that has a password and matches
+the `target`, `service`, and `user` in the search spec, and whose attributes
+match the rest of the spec.  (As with the secret-service store, the label
+can't be searched on.)  The entries returned by a search share their
+credential's data with the entry the credential was originally built for.
+
+To test how your code handles [Ambiguous](Error::Ambiguous) errors,
+use [add_duplicate](MockCredential::add_duplicate) to add
+other items with the same identity as a mock credential.
+
+## Shared mock stores
+
+Because mock credentials keep their data in the entry, two entries
+created separately for the same service and user don't see each other's
+passwords.  If the code you are testing creates entries in different places,
+use a [MockStore] instead: it's an in-memory map from (target, service, user)
+to credential data, and all the credentials built by its
+[credential_builder](MockStore::credential_builder) for the same
+(target, service, user) share the same data.  You can seed the store with
+credentials before a test, inspect it with [snapshot](MockStore::snapshot)
+afterward, and [clear](MockStore::clear) it between tests:
+```rust
+# use keyring::{Entry, mock::{MockKey, MockStore}};
+let store = MockStore::new();
+keyring::set_default_credential_builder(store.credential_builder());
+store.seed(None, "service", "user", b"seeded");
+let entry1 = Entry::new("service", "user").unwrap();
+let entry2 = Entry::new("service", "user").unwrap();
+assert_eq!(entry1.get_password().unwrap(), "seeded");
+entry2.set_password("changed").unwrap();
+assert_eq!(entry1.get_password().unwrap(), "changed");
+let snapshot = store.snapshot();
+assert_eq!(snapshot[&MockKey::new(None, "service", "user")], b"changed");
+store.clear();
+assert!(entry1.get_password().is_err());
+```
+Errors set on a credential from a shared store (see [set_error](MockCredential::set_error))
+are also shared, so they will be returned by the next call on any entry
+for the same (target, service, user).
+
+## Platform profiles
+
+Mock credentials accept any target, service, user, and secret, so tests that
+pass with the mock store can still fail on a platform whose store rejects some
+of those values.  To catch such problems on any platform, build your mocks with
+a [MockProfile] that emulates a platform store's validation rules, size limits,
+attributes, and mapping from entries to credentials, either with
+[profile_credential_builder] or with a [MockStore] made by
+[with_profile](MockStore::with_profile):
+```rust
+# use keyring::{Entry, Error, mock::{self, MockProfile}};
+keyring::set_default_credential_builder(mock::profile_credential_builder(MockProfile::MacOs));
+assert!(matches!(Entry::new("", "user"), Err(Error::Invalid(_, _))));
+keyring::set_default_credential_builder(mock::profile_credential_builder(MockProfile::Windows));
+let entry = Entry::new("service", "user").unwrap();
+assert!(matches!(entry.set_secret(&[0; 4096]), Err(Error::TooLong(_, _))));
+```
+
+## Call recording
+
+Every mock credential keeps a log of the calls made on it, and every mock
+builder (or store) keeps a log of the calls made on it and on the credentials
+it built.  Each logged [MockCall] records the kind of operation, the
+target, service, and user of the credential, whether the call succeeded,
+and when it was made.  Secrets are never logged; instead, the log keeps
+a hash that tests can compare against an expected secret.
+The [MockCalls] returned by the `calls` methods have assertion helpers:
+```rust
+# use keyring::{Entry, mock::{MockOp, MockStore}};
+let store = MockStore::new();
+let entry = Entry::new_with_credential(store.credential_builder().build(None, "svc", "user").unwrap());
+entry.set_password("secret").unwrap();
+entry.get_password().unwrap();
+let calls = store.calls();
+calls.assert_count(MockOp::GetPassword, 1);
+calls.assert_never(MockOp::DeleteCredential);
+assert!(calls[1].secret_matches(b"secret"));
 ```
  */
 use std::cell::RefCell;
-use std::sync::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use super::credential::{
-    Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi, CredentialPersistence,
+    Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi, CredentialCapabilities,
+    CredentialPersistence,
 };
-use super::error::{Error, Result, decode_password};
+use super::error::{Error, Result};
+use super::secret::{SecretBytes, SecretString, decode_zeroizing_password};
 
 /// The concrete mock credential
 ///
 /// Mocks use an internal mutability pattern since entries are read-only.
-/// The mutex is used to make sure these are Sync.
-#[derive(Debug)]
+/// The mutex is used to make sure these are Sync, and the data is
+/// reference-counted so that entries found by a search can share it.
+#[derive(Debug, Default)]
 pub struct MockCredential {
-    pub inner: Mutex<RefCell<MockData>>,
-}
-
-impl Default for MockCredential {
-    fn default() -> Self {
-        Self {
-            inner: Mutex::new(RefCell::new(Default::default())),
-        }
-    }
+    pub inner: Arc<Mutex<RefCell<MockData>>>,
+    pub target: Option<String>,
+    pub service: String,
+    pub user: String,
+    /// The log of the builder that built this credential, if any
+    pub builder_log: Option<MockLog>,
+    /// The platform store this credential emulates
+    pub profile: MockProfile,
 }
 
 /// The (in-memory) persisted data for a mock credential.
 ///
-/// We keep a password, but unlike most keystores
-/// we also keep an intended error to return on the next call.
+/// We keep a password (in a buffer that is zeroed when the password
+/// is replaced or deleted) and the credential's attributes
+/// (including its label), along with the data for other items
+/// that have the same identity (see [add_duplicate](MockCredential::add_duplicate)).
+/// But unlike most keystores
+/// we also keep an intended error to return on the next call,
+/// scripted errors and latencies for specific operations,
+/// a random error generator, and a log of the calls made on the credential.
 ///
 /// (Everything about this structure is public for transparency.
 /// Most keystore implementation hide their internals.)
 #[derive(Debug, Default)]
 pub struct MockData {
-    pub secret: Option<Vec<u8>>,
+    pub secret: Option<SecretBytes>,
     pub error: Option<Error>,
+    pub scripted_errors: HashMap<MockOp, VecDeque<Error>>,
+    pub chaos: Option<MockChaos>,
+    pub latency: HashMap<MockOp, Duration>,
+    pub calls: Vec<MockCall>,
+    pub attributes: HashMap<String, String>,
+    pub duplicates: Vec<SharedData>,
+    pub locked: bool,
+}
+
+impl MockData {
+    /// Store a secret, giving the credential its initial attributes if it's new.
+    fn store_secret(&mut self, profile: MockProfile, key: &MockKey, secret: &[u8]) {
+        if self.secret.is_none() {
+            self.attributes = profile.initial_attributes(key);
+        }
+        self.secret = Some(SecretBytes::new(secret.to_vec()));
+    }
+
+    /// Take the error, if any, that the given operation should fail with.
+    ///
+    /// An error set with [set_error](MockCredential::set_error) comes first,
+    /// then errors scripted for the operation, then random chaos errors.
+    fn take_error(&mut self, op: MockOp) -> Option<Error> {
+        if let Some(err) = self.error.take() {
+            return Some(err);
+        }
+        if let Some(err) = self
+            .scripted_errors
+            .get_mut(&op)
+            .and_then(VecDeque::pop_front)
+        {
+            return Some(err);
+        }
+        self.chaos.as_mut().and_then(|chaos| chaos.roll(op))
+    }
+}
+
+/// Spread the bits of a seed (with splitmix64) to make a nonzero generator state.
+fn mix_seed(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)).max(1)
+}
+
+/// A generator of random failures for a mock credential.
+///
+/// Each operation fails with the given probability (between 0 and 1),
+/// with the error made by the given function.  The random sequence is
+/// determined by the seed, so failing tests can be reproduced.
+#[derive(Debug, Clone)]
+pub struct MockChaos {
+    pub probability: f64,
+    pub error: fn(MockOp) -> Error,
+    state: u64,
+}
+
+impl MockChaos {
+    /// Make a generator with the given failure probability, seed, and error maker.
+    pub fn new(probability: f64, seed: u64, error: fn(MockOp) -> Error) -> Self {
+        Self {
+            probability,
+            error,
+            state: mix_seed(seed),
+        }
+    }
+
+    /// Decide whether an operation fails, and if so with what error.
+    fn roll(&mut self, op: MockOp) -> Option<Error> {
+        // xorshift64*, which is plenty random for a mock
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let sample = self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        let sample = sample as f64 / (1u64 << 53) as f64;
+        (sample < self.probability).then(|| (self.error)(op))
+    }
+}
+
+/// The kinds of operation recorded in mock call logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MockOp {
+    Build,
+    Search,
+    SetPassword,
+    SetSecret,
+    SetSecretIf,
+    GetPassword,
+    GetSecret,
+    GetZeroizingPassword,
+    GetZeroizingSecret,
+    GetAttributes,
+    UpdateAttributes,
+    DeleteCredential,
+    IsLocked,
+    Unlock,
+    Lock,
+}
+
+impl MockOp {
+    /// All the operations on mock credentials (not builders).
+    pub const CREDENTIAL_OPS: &'static [MockOp] = &[
+        MockOp::SetPassword,
+        MockOp::SetSecret,
+        MockOp::SetSecretIf,
+        MockOp::GetPassword,
+        MockOp::GetSecret,
+        MockOp::GetZeroizingPassword,
+        MockOp::GetZeroizingSecret,
+        MockOp::GetAttributes,
+        MockOp::UpdateAttributes,
+        MockOp::DeleteCredential,
+        MockOp::IsLocked,
+        MockOp::Unlock,
+        MockOp::Lock,
+    ];
+}
+
+/// A call recorded in a mock call log.
+///
+/// Secrets passed to the mock are never logged.  Instead, a hash of the
+/// secret is kept, so tests can check which secret was passed by using
+/// [secret_matches](MockCall::secret_matches).  (The hash is not
+/// cryptographically secure, so don't log it anywhere either.)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockCall {
+    /// The kind of operation
+    pub op: MockOp,
+    /// The identity of the credential (or `None` for searches)
+    pub key: Option<MockKey>,
+    /// The hash of the secret passed to the call, if any
+    pub secret_hash: Option<u64>,
+    /// Whether the call returned success
+    pub succeeded: bool,
+    /// When the call was made
+    pub time: std::time::SystemTime,
+}
+
+impl MockCall {
+    fn new(op: MockOp, key: Option<MockKey>, secret: Option<&[u8]>, succeeded: bool) -> Self {
+        Self {
+            op,
+            key,
+            secret_hash: secret.map(hash_secret),
+            succeeded,
+            time: std::time::SystemTime::now(),
+        }
+    }
+
+    /// Whether the call was passed the given secret (or password bytes).
+    pub fn secret_matches(&self, secret: &[u8]) -> bool {
+        self.secret_hash == Some(hash_secret(secret))
+    }
+}
+
+fn hash_secret(secret: &[u8]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    secret.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A sequence of recorded calls, oldest first, with assertion helpers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MockCalls(pub Vec<MockCall>);
+
+impl std::ops::Deref for MockCalls {
+    type Target = [MockCall];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl MockCalls {
+    /// The number of calls of the given kind.
+    pub fn count(&self, op: MockOp) -> usize {
+        self.0.iter().filter(|call| call.op == op).count()
+    }
+
+    /// The kinds of all the calls, in order.
+    pub fn ops(&self) -> Vec<MockOp> {
+        self.0.iter().map(|call| call.op).collect()
+    }
+
+    /// Panic unless there were exactly `expected` calls of the given kind.
+    #[track_caller]
+    pub fn assert_count(&self, op: MockOp, expected: usize) {
+        let actual = self.count(op);
+        assert_eq!(
+            actual,
+            expected,
+            "Expected {expected} {op:?} calls but there were {actual}; calls were {:?}",
+            self.ops()
+        );
+    }
+
+    /// Panic if there were any calls of the given kind.
+    #[track_caller]
+    pub fn assert_never(&self, op: MockOp) {
+        self.assert_count(op, 0)
+    }
+}
+
+/// A shared log of mock calls, kept by mock builders and stores.
+///
+/// Logs are cheap to clone: all the clones share the same calls.
+#[derive(Debug, Clone, Default)]
+pub struct MockLog {
+    calls: Arc<Mutex<Vec<MockCall>>>,
+}
+
+impl MockLog {
+    /// The calls recorded in this log.
+    pub fn calls(&self) -> MockCalls {
+        MockCalls(self.calls.lock().expect("Can't access mock log").clone())
+    }
+
+    /// Forget all the calls recorded in this log.
+    pub fn clear(&self) {
+        self.calls.lock().expect("Can't access mock log").clear()
+    }
+
+    fn push(&self, call: MockCall) {
+        self.calls.lock().expect("Can't access mock log").push(call)
+    }
 }
 
 impl CredentialApi for MockCredential {
@@ -78,16 +405,23 @@ impl CredentialApi for MockCredential {
     /// and the password will _not_ be set.  The error will
     /// be cleared, so calling again will set the password.
     fn set_password(&self, password: &str) -> Result<()> {
+        self.delay(MockOp::SetPassword);
         let mut inner = self.inner.lock().expect("Can't access mock data for set");
         let data = inner.get_mut();
-        let err = data.error.take();
-        match err {
-            None => {
-                data.secret = Some(password.as_bytes().to_vec());
-                Ok(())
-            }
+        let result = match self.take_error(data, MockOp::SetPassword) {
+            None => self
+                .profile
+                .validate_secret(password.as_bytes(), true)
+                .map(|()| self.store_secret(data, password.as_bytes())),
             Some(err) => Err(err),
-        }
+        };
+        self.record(
+            data,
+            MockOp::SetPassword,
+            Some(password.as_bytes()),
+            &result,
+        );
+        result
     }
 
     /// Set a password on a mock credential.
@@ -96,16 +430,39 @@ impl CredentialApi for MockCredential {
     /// and the password will _not_ be set.  The error will
     /// be cleared, so calling again will set the password.
     fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.delay(MockOp::SetSecret);
         let mut inner = self.inner.lock().expect("Can't access mock data for set");
         let data = inner.get_mut();
-        let err = data.error.take();
-        match err {
-            None => {
-                data.secret = Some(secret.to_vec());
-                Ok(())
-            }
+        let result = match self.take_error(data, MockOp::SetSecret) {
+            None => self
+                .profile
+                .validate_secret(secret, false)
+                .map(|()| self.store_secret(data, secret)),
             Some(err) => Err(err),
-        }
+        };
+        self.record(data, MockOp::SetSecret, Some(secret), &result);
+        result
+    }
+
+    /// Set a secret on a mock credential if its current secret is the expected one.
+    ///
+    /// Mocks do the comparison and the set while holding their data's lock,
+    /// so this is atomic.  If there is an error in the mock, it will be
+    /// returned and the secret will _not_ be set.
+    fn set_secret_if(&self, expected: Option<&[u8]>, secret: &[u8]) -> Result<()> {
+        self.delay(MockOp::SetSecretIf);
+        let mut inner = self.inner.lock().expect("Can't access mock data for set");
+        let data = inner.get_mut();
+        let result = match self.take_error(data, MockOp::SetSecretIf) {
+            None if data.secret.as_ref().map(|s| s.expose_secret()) == expected => self
+                .profile
+                .validate_secret(secret, false)
+                .map(|()| self.store_secret(data, secret)),
+            None => Err(Error::Conflict),
+            Some(err) => Err(err),
+        };
+        self.record(data, MockOp::SetSecretIf, Some(secret), &result);
+        result
     }
 
     /// Get the password from a mock credential, if any.
@@ -113,33 +470,90 @@ impl CredentialApi for MockCredential {
     /// If there is an error set in the mock, it will
     /// be returned instead of a password.
     fn get_password(&self) -> Result<String> {
+        self.get_zeroizing(MockOp::GetPassword, |secret| {
+            Ok(decode_zeroizing_password(secret)?
+                .expose_secret()
+                .to_string())
+        })
+    }
+
+    /// Get the password from a mock credential, if any.
+    ///
+    /// If there is an error set in the mock, it will
+    /// be returned instead of a password.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.get_zeroizing(MockOp::GetSecret, |secret| {
+            Ok(secret.expose_secret().to_vec())
+        })
+    }
+
+    /// Get the password from a mock credential, if any, in a zeroizing buffer.
+    ///
+    /// If there is an error set in the mock, it will
+    /// be returned instead of a password.
+    fn get_zeroizing_password(&self) -> Result<SecretString> {
+        self.get_zeroizing(MockOp::GetZeroizingPassword, decode_zeroizing_password)
+    }
+
+    /// Get the secret from a mock credential, if any, in a zeroizing buffer.
+    ///
+    /// If there is an error set in the mock, it will
+    /// be returned instead of a secret.
+    fn get_zeroizing_secret(&self) -> Result<SecretBytes> {
+        self.get_zeroizing(MockOp::GetZeroizingSecret, Ok)
+    }
+
+    /// Get the attributes of a mock credential.
+    ///
+    /// As with the secret-service store, the attributes include the
+    /// credential's label but not its target, service, or user.
+    /// If there is an error set in the mock, it will be returned.
+    /// Otherwise, if there is no password, a [NoEntry](Error::NoEntry)
+    /// error will be returned.
+    fn get_attributes(&self) -> Result<HashMap<String, String>> {
+        self.delay(MockOp::GetAttributes);
         let mut inner = self.inner.lock().expect("Can't access mock data for get");
         let data = inner.get_mut();
-        let err = data.error.take();
-        match err {
+        let result = match self.take_error(data, MockOp::GetAttributes) {
             None => match &data.secret {
                 None => Err(Error::NoEntry),
-                Some(val) => decode_password(val.clone()),
+                Some(_) => Ok(data.attributes.clone()),
             },
             Some(err) => Err(err),
-        }
+        };
+        self.record(data, MockOp::GetAttributes, None, &result);
+        result
     }
 
-    /// Get the password from a mock credential, if any.
+    /// Update the attributes of a mock credential.
     ///
-    /// If there is an error set in the mock, it will
-    /// be returned instead of a password.
-    fn get_secret(&self) -> Result<Vec<u8>> {
-        let mut inner = self.inner.lock().expect("Can't access mock data for get");
+    /// As with the secret-service store, updates to the `target`, `service`,
+    /// and `username` attributes are ignored, and an empty label is rejected
+    /// with an [Invalid](Error::Invalid) error (in which case nothing is updated).
+    /// Mocks with a platform [profile](MockProfile) update attributes
+    /// the way that platform's store does.
+    /// If there is an error set in the mock, it will be returned.
+    /// Otherwise, if there is no password, a [NoEntry](Error::NoEntry)
+    /// error will be returned.
+    fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
+        self.delay(MockOp::UpdateAttributes);
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("Can't access mock data for update");
         let data = inner.get_mut();
-        let err = data.error.take();
-        match err {
+        let result = match self.take_error(data, MockOp::UpdateAttributes) {
             None => match &data.secret {
                 None => Err(Error::NoEntry),
-                Some(val) => Ok(val.clone()),
+                Some(_) => {
+                    self.profile
+                        .update_attributes(&self.key(), &mut data.attributes, attributes)
+                }
             },
             Some(err) => Err(err),
-        }
+        };
+        self.record(data, MockOp::UpdateAttributes, None, &result);
+        result
     }
 
     /// Delete the password in a mock credential
@@ -150,22 +564,63 @@ impl CredentialApi for MockCredential {
     /// If there is no password, a [NoEntry](Error::NoEntry) error
     /// will be returned.
     fn delete_credential(&self) -> Result<()> {
+        self.delay(MockOp::DeleteCredential);
         let mut inner = self
             .inner
             .lock()
             .expect("Can't access mock data for delete");
         let data = inner.get_mut();
-        let err = data.error.take();
-        match err {
+        let result = match self.take_error(data, MockOp::DeleteCredential) {
             None => match data.secret {
                 Some(_) => {
                     data.secret = None;
+                    data.attributes.clear();
                     Ok(())
                 }
                 None => Err(Error::NoEntry),
             },
             Some(err) => Err(err),
-        }
+        };
+        self.record(data, MockOp::DeleteCredential, None, &result);
+        result
+    }
+
+    /// Check whether a mock credential is locked.
+    ///
+    /// Mocks whose [profile](MockProfile) can't be locked are never locked.
+    /// If there is an error set in the mock, it will be returned.
+    fn is_locked(&self) -> Result<bool> {
+        self.lock_op(MockOp::IsLocked, |data| Ok(data.locked))
+    }
+
+    /// Unlock a mock credential.
+    ///
+    /// This never prompts, and does nothing if the mock's
+    /// [profile](MockProfile) can't be locked.
+    /// If there is an error set in the mock, it will be returned.
+    fn unlock(&self) -> Result<()> {
+        self.lock_op(MockOp::Unlock, |data| {
+            data.locked = false;
+            Ok(())
+        })
+    }
+
+    /// Lock a mock credential.
+    ///
+    /// Until it's unlocked, other calls on a locked mock fail
+    /// with a [Locked](Error::Locked) error, as they would in a store
+    /// that was told never to prompt.  Mocks whose [profile](MockProfile)
+    /// can't be locked return a [NotSupportedByStore](Error::NotSupportedByStore) error.
+    /// If there is an error set in the mock, it will be returned.
+    fn lock(&self) -> Result<()> {
+        let locking = self.profile.capabilities().locking;
+        self.lock_op(MockOp::Lock, |data| {
+            if !locking {
+                return Err(Error::NotSupportedByStore("lock".to_string()));
+            }
+            data.locked = true;
+            Ok(())
+        })
     }
 
     /// Return this mock credential concrete object
@@ -186,120 +641,1300 @@ impl MockCredential {
     ///
     /// Since mocks have no persistence between sessions,
     /// new mocks always have no password.
-    fn new_with_target(_target: Option<&str>, _service: &str, _user: &str) -> Result<Self> {
-        Ok(Default::default())
+    fn new_with_target(target: Option<&str>, service: &str, user: &str) -> Result<Self> {
+        Ok(Self {
+            inner: Default::default(),
+            target: target.map(|t| t.to_string()),
+            service: service.to_string(),
+            user: user.to_string(),
+            builder_log: None,
+            profile: MockProfile::Generic,
+        })
     }
 
-    /// Set an error to be returned from this mock credential.
-    ///
-    /// Error returns always take precedence over the normal
-    /// behavior of the mock.  But once an error has been
-    /// returned it is removed, so the mock works thereafter.
-    pub fn set_error(&self, err: Error) {
-        let mut inner = self
-            .inner
-            .lock()
-            .expect("Can't access mock data for set_error");
-        let data = inner.get_mut();
-        data.error = Some(err);
+    /// Make another credential with the same identity that shares this one's data.
+    fn share(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            target: self.target.clone(),
+            service: self.service.clone(),
+            user: self.user.clone(),
+            builder_log: self.builder_log.clone(),
+            profile: self.profile,
+        }
     }
-}
 
-/// The builder for mock credentials.
-pub struct MockCredentialBuilder {}
+    /// The identity of this credential.
+    fn key(&self) -> MockKey {
+        MockKey::new(self.target.as_deref(), &self.service, &self.user)
+    }
 
-impl CredentialBuilderApi for MockCredentialBuilder {
-    /// Build a mock credential for the given target, service, and user.
+    /// Make a credential with this one's identity for the given data.
+    fn with_data(&self, inner: SharedData) -> Self {
+        Self {
+            inner,
+            ..self.share()
+        }
+    }
+
+    /// Store a secret in this credential's data.
+    fn store_secret(&self, data: &mut MockData, secret: &[u8]) {
+        data.store_secret(self.profile, &self.key(), secret)
+    }
+
+    /// Take the error, if any, that the given operation should fail with.
     ///
-    /// Since mocks don't persist between sessions,  all mocks
-    /// start off without passwords.
-    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
-        let credential = MockCredential::new_with_target(target, service, user)?;
-        Ok(Box::new(credential))
+    /// This is either an error set on the mock, a [Locked](Error::Locked)
+    /// error if the mock is locked, or an [Ambiguous](Error::Ambiguous)
+    /// error if there are duplicate items for this credential.
+    fn take_error(&self, data: &mut MockData, op: MockOp) -> Option<Error> {
+        data.take_error(op)
+            .or_else(|| data.locked.then_some(Error::Locked))
+            .or_else(|| {
+                let items = self.items(data);
+                (items.len() > 1).then(|| {
+                    Error::Ambiguous(
+                        items
+                            .into_iter()
+                            .map(|item| Box::new(item) as Box<Credential>)
+                            .collect(),
+                    )
+                })
+            })
     }
 
-    /// Get an [Any][std::any::Any] reference to the mock credential builder.
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
+    /// Do a locking operation, recording it.
+    ///
+    /// Locking operations work on the mock's lock state, so they fail only
+    /// with errors set on the mock, not because the mock is locked or ambiguous.
+    fn lock_op<T>(&self, op: MockOp, f: impl FnOnce(&mut MockData) -> Result<T>) -> Result<T> {
+        self.delay(op);
+        let mut inner = self.inner.lock().expect("Can't access mock data for lock");
+        let data = inner.get_mut();
+        let result = match data.take_error(op) {
+            None => f(data),
+            Some(err) => Err(err),
+        };
+        self.record(data, op, None, &result);
+        result
     }
 
-    /// This keystore keeps the password in the entry!
-    fn persistence(&self) -> CredentialPersistence {
-        CredentialPersistence::EntryOnly
+    /// Credentials for the existing items (this one and its duplicates) with this identity.
+    fn items(&self, data: &MockData) -> Vec<MockCredential> {
+        let mut items = vec![];
+        if data.secret.is_some() {
+            items.push(self.share());
+        }
+        for duplicate in data.duplicates.iter() {
+            let inner = duplicate
+                .lock()
+                .expect("Can't access mock data for duplicate");
+            if inner.borrow().secret.is_some() {
+                items.push(self.with_data(duplicate.clone()));
+            }
+        }
+        items
     }
-}
 
-/// Return a mock credential builder for use by clients.
-pub fn default_credential_builder() -> Box<CredentialBuilder> {
-    Box::new(MockCredentialBuilder {})
-}
+    /// Credentials for the existing items with this identity, for searches.
+    fn found_items(&self) -> Vec<MockCredential> {
+        let inner = self
+            .inner
+            .lock()
+            .expect("Can't access mock data for search");
+        self.items(&inner.borrow())
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::{MockCredential, default_credential_builder};
-    use crate::credential::CredentialPersistence;
-    use crate::{Entry, Error, tests::generate_random_string};
+    /// Record a call in this credential's log and its builder's log.
+    fn record<T>(
+        &self,
+        data: &mut MockData,
+        op: MockOp,
+        secret: Option<&[u8]>,
+        result: &Result<T>,
+    ) {
+        let call = MockCall::new(op, Some(self.key()), secret, result.is_ok());
+        if let Some(log) = &self.builder_log {
+            log.push(call.clone());
+        }
+        data.calls.push(call);
+    }
 
-    #[test]
-    fn test_persistence() {
-        assert!(matches!(
-            default_credential_builder().persistence(),
-            CredentialPersistence::EntryOnly
-        ))
+    /// Wait for the latency, if any, set for the given operation.
+    fn delay(&self, op: MockOp) {
+        let latency = {
+            let inner = self.inner.lock().expect("Can't access mock data for delay");
+            inner.borrow().latency.get(&op).copied()
+        };
+        if let Some(latency) = latency {
+            std::thread::sleep(latency);
+        }
     }
 
-    fn entry_new(service: &str, user: &str) -> Entry {
-        let credential = MockCredential::new_with_target(None, service, user).unwrap();
-        Entry::new_with_credential(Box::new(credential))
+    /// Get the secret, if any, in a zeroizing buffer, and convert it
+    /// with the given function, recording the given operation.
+    ///
+    /// All the get calls go through here, so the only copies of the secret
+    /// they make outside of a zeroizing buffer are the ones they return.
+    fn get_zeroizing<T>(&self, op: MockOp, f: impl FnOnce(SecretBytes) -> Result<T>) -> Result<T> {
+        self.delay(op);
+        let mut inner = self.inner.lock().expect("Can't access mock data for get");
+        let data = inner.get_mut();
+        let result = match self.take_error(data, op) {
+            None => match &data.secret {
+                None => Err(Error::NoEntry),
+                Some(val) => f(val.clone()),
+            },
+            Some(err) => Err(err),
+        };
+        self.record(data, op, None, &result);
+        result
     }
 
-    #[test]
-    fn test_missing_entry() {
-        crate::tests::test_missing_entry(entry_new);
+    /// The calls made on this credential (and any credentials sharing its data).
+    pub fn calls(&self) -> MockCalls {
+        let inner = self.inner.lock().expect("Can't access mock data for calls");
+        MockCalls(inner.borrow().calls.clone())
     }
 
-    #[test]
-    fn test_empty_password() {
-        crate::tests::test_empty_password(entry_new);
+    /// Forget the calls made on this credential.
+    pub fn clear_calls(&self) {
+        let mut inner = self.inner.lock().expect("Can't access mock data for calls");
+        inner.get_mut().calls.clear()
     }
 
-    #[test]
-    fn test_round_trip_ascii_password() {
-        crate::tests::test_round_trip_ascii_password(entry_new);
+    /// Check whether some other credential is sharing this one's data.
+    fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.inner) > 1
     }
 
-    #[test]
-    fn test_round_trip_non_ascii_password() {
-        crate::tests::test_round_trip_non_ascii_password(entry_new);
+    /// Check whether this credential has a password and matches a search spec.
+    fn matches(&self, spec: &HashMap<&str, &str>) -> bool {
+        let inner = self
+            .inner
+            .lock()
+            .expect("Can't access mock data for search");
+        let data = inner.borrow();
+        data.secret.is_some()
+            && spec.iter().all(|(key, value)| match *key {
+                "target" => self.target.as_deref() == Some(*value),
+                "service" => self.service == *value,
+                "user" => self.user == *value,
+                // as with the secret service, the label can't be searched on
+                "label" => false,
+                _ => data.attributes.get(*key).is_some_and(|v| v == value),
+            })
     }
 
-    #[test]
-    fn test_round_trip_random_secret() {
-        crate::tests::test_round_trip_random_secret(entry_new);
+    /// Add another item with this credential's identity and the given secret,
+    /// as a third-party application might.
+    ///
+    /// While there is more than one item with this credential's identity
+    /// (counting this one, if it has a secret), every call on this
+    /// credential fails with an [Ambiguous](Error::Ambiguous) error
+    /// that holds a credential for each item, this one first.  (The
+    /// credential for this one will still be ambiguous, but those for the
+    /// others will not be, so deleting them resolves the ambiguity.)
+    /// Searches find all the items.
+    pub fn add_duplicate(&self, secret: &[u8]) {
+        let mut duplicate = MockData::default();
+        duplicate.store_secret(self.profile, &self.key(), secret);
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("Can't access mock data for add_duplicate");
+        inner
+            .get_mut()
+            .duplicates
+            .push(Arc::new(Mutex::new(RefCell::new(duplicate))));
     }
 
-    #[test]
-    fn test_update() {
-        crate::tests::test_update(entry_new);
+    /// Set an error to be returned from this mock credential.
+    ///
+    /// Error returns always take precedence over the normal
+    /// behavior of the mock.  But once an error has been
+    /// returned it is removed, so the mock works thereafter.
+    pub fn set_error(&self, err: Error) {
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("Can't access mock data for set_error");
+        let data = inner.get_mut();
+        data.error = Some(err);
     }
 
-    #[test]
-    fn test_get_update_attributes() {
-        crate::tests::test_noop_get_update_attributes(entry_new);
+    /// Script errors to be returned from the next calls of the given operation.
+    ///
+    /// Each call of the operation returns (and removes) the next scripted error,
+    /// until there are none left, after which the operation works as usual.
+    /// For example, to fail the next two `set_secret` calls:
+    /// ```rust
+    /// # use keyring::{Error, mock::{MockCredential, MockOp}};
+    /// # let mock = MockCredential::default();
+    /// let unavailable = || Error::NoStorageAccess("keyring is locked".into());
+    /// mock.script_errors(MockOp::SetSecret, std::iter::repeat_with(unavailable).take(2));
+    /// ```
+    /// Scripted errors are added after any that are already scripted.
+    /// An error set with [set_error](MockCredential::set_error) takes precedence.
+    pub fn script_errors(&self, op: MockOp, errors: impl IntoIterator<Item = Error>) {
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("Can't access mock data for script_errors");
+        let data = inner.get_mut();
+        data.scripted_errors.entry(op).or_default().extend(errors);
     }
 
-    #[test]
-    fn test_set_error() {
-        let name = generate_random_string();
-        let entry = entry_new(&name, &name);
-        let password = "test ascii password";
-        let mock: &MockCredential = entry
+    /// Make calls on this mock credential fail at random.
+    ///
+    /// See [MockChaos] for details.  Pass `None` to stop the random failures.
+    /// Errors set with [set_error](MockCredential::set_error) or
+    /// [script_errors](MockCredential::script_errors) take precedence.
+    pub fn set_chaos(&self, chaos: Option<MockChaos>) {
+        let mut inner = self
             .inner
-            .as_any()
-            .downcast_ref()
-            .expect("Downcast failed");
-        mock.set_error(Error::Invalid(
-            "mock error".to_string(),
+            .lock()
+            .expect("Can't access mock data for set_chaos");
+        inner.get_mut().chaos = chaos;
+    }
+
+    /// Make every call of the given operation wait before doing anything.
+    ///
+    /// Pass a zero duration to remove the latency.  Use [MockOp::CREDENTIAL_OPS]
+    /// to slow down every operation.  The wait happens without holding the
+    /// credential's lock, so concurrent calls wait concurrently.
+    pub fn set_latency(&self, op: MockOp, latency: Duration) {
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("Can't access mock data for set_latency");
+        let data = inner.get_mut();
+        if latency.is_zero() {
+            data.latency.remove(&op);
+        } else {
+            data.latency.insert(op, latency);
+        }
+    }
+}
+
+/// The key for a credential in a [MockStore].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MockKey {
+    pub target: Option<String>,
+    pub service: String,
+    pub user: String,
+}
+
+impl MockKey {
+    /// Make a key for the given target, service, and user.
+    pub fn new(target: Option<&str>, service: &str, user: &str) -> Self {
+        Self {
+            target: target.map(|t| t.to_string()),
+            service: service.to_string(),
+            user: user.to_string(),
+        }
+    }
+}
+
+/// The platform credential store whose behavior a mock emulates.
+///
+/// Mocks with the [Generic](MockProfile::Generic) profile accept
+/// any target, service, and user, and any secret.  Mocks with
+/// one of the other profiles behave like the named store in these ways:
+///
+/// - They reject the same target, service, and user values that the store
+///   does, with the same errors.  For example, the Windows profile returns a
+///   [TooLong](Error::TooLong) error for a user name longer than 513 bytes
+///   (or a service name so long that the credential's comment would be
+///   longer than 256 bytes), and the macOS profile returns an
+///   [Invalid](Error::Invalid) error for an empty service or user.
+/// - They reject the same secrets that the store does.  (Only the
+///   Windows store limits the size of secrets: passwords are limited to
+///   2560 bytes when encoded as UTF-16, and binary secrets to 2560 bytes.)
+/// - They map the target, service, and user to a credential
+///   the same way the store does, so entries that would share a credential
+///   on the platform share data in a [MockStore].  For example, with the
+///   Windows profile, all entries with the same target share a credential,
+///   whatever their service and user.
+/// - Their credentials have the same attributes as the store's credentials
+///   (none, for the macOS and iOS profiles), and updates to those
+///   attributes are validated the way the store validates them.
+/// - Their builder reports the store's [capabilities](CredentialBuilderApi::capabilities),
+///   and only does searches if the store does.
+///
+/// Apart from that, profiled mocks behave like other mocks: you can set errors
+/// on them, they record their calls, and so on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MockProfile {
+    /// Accept anything (the default)
+    #[default]
+    Generic,
+    /// Behave like the secret-service store
+    SecretService,
+    /// Behave like the Windows Credential Manager store
+    Windows,
+    /// Behave like the macOS keychain store
+    MacOs,
+    /// Behave like the iOS keychain store
+    Ios,
+}
+
+// The Windows Credential Manager limits (from `wincred.h`).
+const WIN_MAX_USERNAME_LENGTH: u32 = 513;
+const WIN_MAX_GENERIC_TARGET_NAME_LENGTH: u32 = 32767;
+const WIN_MAX_STRING_LENGTH: u32 = 256;
+const WIN_MAX_CREDENTIAL_BLOB_SIZE: u32 = 5 * 512;
+
+impl MockProfile {
+    /// Check that the store would build a credential for the given identity.
+    fn validate(self, target: Option<&str>, service: &str, user: &str) -> Result<()> {
+        let empty = |name: &str| {
+            Err(Error::Invalid(
+                name.to_string(),
+                "cannot be empty".to_string(),
+            ))
+        };
+        match self {
+            MockProfile::Generic => Ok(()),
+            MockProfile::SecretService if target == Some("") => empty("target"),
+            MockProfile::SecretService => Ok(()),
+            MockProfile::Windows => {
+                let attributes = self.initial_attributes(&MockKey::new(target, service, user));
+                validate_windows_attributes(&self.key(target, service, user), &attributes)
+            }
+            MockProfile::MacOs | MockProfile::Ios if service.is_empty() => empty("service"),
+            MockProfile::MacOs | MockProfile::Ios if user.is_empty() => empty("user"),
+            MockProfile::MacOs => Ok(()),
+            MockProfile::Ios => match target {
+                Some(target) if !target.eq_ignore_ascii_case("default") => Err(Error::Invalid(
+                    "target".to_string(),
+                    "only 'default' is allowed".to_string(),
+                )),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// The identity of the store's credential for the given target, service, and user.
+    fn key(self, target: Option<&str>, service: &str, user: &str) -> MockKey {
+        match self {
+            MockProfile::Generic => MockKey::new(target, service, user),
+            MockProfile::SecretService => {
+                MockKey::new(Some(target.unwrap_or("default")), service, user)
+            }
+            MockProfile::Windows => match target {
+                Some(target) => MockKey::new(Some(target), "", ""),
+                None => MockKey::new(Some(&format!("{user}.{service}")), "", ""),
+            },
+            MockProfile::MacOs => {
+                let domain = match target.map(str::to_ascii_lowercase).as_deref() {
+                    Some("system") => "System",
+                    Some("common") => "Common",
+                    Some("dynamic") => "Dynamic",
+                    Some("protected" | "data protection") => "Protected",
+                    _ => "User",
+                };
+                MockKey::new(Some(domain), service, user)
+            }
+            MockProfile::Ios => MockKey::new(None, service, user),
+        }
+    }
+
+    /// The attributes the store gives a new credential with the given identity.
+    fn initial_attributes(self, key: &MockKey) -> HashMap<String, String> {
+        const VERSION: &str = env!("CARGO_PKG_VERSION");
+        let (target, service, user) = (key.target.as_deref(), &key.service, &key.user);
+        match self {
+            MockProfile::Generic | MockProfile::SecretService => HashMap::from([
+                ("application".to_string(), "rust-keyring".to_string()),
+                (
+                    "label".to_string(),
+                    format!(
+                        "{user}@{service}:{} (keyring mock v{VERSION})",
+                        target.unwrap_or("default"),
+                    ),
+                ),
+            ]),
+            MockProfile::Windows => {
+                let target_name = self.key(target, service, user).target.unwrap_or_default();
+                HashMap::from([
+                    ("username".to_string(), user.to_string()),
+                    ("target_alias".to_string(), String::new()),
+                    (
+                        "comment".to_string(),
+                        format!("{user}@{service}:{target_name} (keyring v{VERSION})"),
+                    ),
+                ])
+            }
+            MockProfile::MacOs | MockProfile::Ios => HashMap::new(),
+        }
+    }
+
+    /// Check that the store would accept the given secret.
+    fn validate_secret(self, secret: &[u8], is_password: bool) -> Result<()> {
+        if self != MockProfile::Windows {
+            return Ok(());
+        }
+        if is_password {
+            // Windows stores passwords as UTF-16
+            let password = String::from_utf8_lossy(secret);
+            if password.encode_utf16().count() * 2 > WIN_MAX_CREDENTIAL_BLOB_SIZE as usize {
+                return Err(Error::TooLong(
+                    "password encoded as UTF-16".to_string(),
+                    WIN_MAX_CREDENTIAL_BLOB_SIZE,
+                ));
+            }
+        } else if secret.len() > WIN_MAX_CREDENTIAL_BLOB_SIZE as usize {
+            return Err(Error::TooLong(
+                "secret".to_string(),
+                WIN_MAX_CREDENTIAL_BLOB_SIZE,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Update a credential's attributes the way the store does.
+    ///
+    /// If the update is rejected, the attributes are unchanged.
+    fn update_attributes(
+        self,
+        key: &MockKey,
+        current: &mut HashMap<String, String>,
+        updates: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        match self {
+            MockProfile::Generic | MockProfile::SecretService => {
+                if updates.get("label").is_some_and(|l| l.is_empty()) {
+                    return Err(Error::Invalid(
+                        "label".to_string(),
+                        "cannot be empty".to_string(),
+                    ));
+                }
+                for (name, value) in updates.iter() {
+                    if !matches!(*name, "target" | "service" | "username") {
+                        current.insert(name.to_string(), value.to_string());
+                    }
+                }
+                Ok(())
+            }
+            MockProfile::Windows => {
+                let mut updated = current.clone();
+                for (name, value) in updates.iter() {
+                    if matches!(*name, "comment" | "target_alias" | "username") {
+                        updated.insert(name.to_string(), value.to_string());
+                    }
+                }
+                let key = self.key(key.target.as_deref(), &key.service, &key.user);
+                validate_windows_attributes(&key, &updated)?;
+                *current = updated;
+                Ok(())
+            }
+            MockProfile::MacOs | MockProfile::Ios => Ok(()),
+        }
+    }
+
+    /// The capabilities of the store.
+    fn capabilities(self) -> CredentialCapabilities {
+        match self {
+            MockProfile::Generic => CredentialCapabilities {
+                attributes: true,
+                binary_secrets: true,
+                empty_service: true,
+                empty_user: true,
+                search: true,
+                atomic_updates: true,
+                locking: true,
+                ..Default::default()
+            },
+            MockProfile::SecretService => CredentialCapabilities {
+                attributes: true,
+                binary_secrets: true,
+                empty_service: true,
+                empty_user: true,
+                search: true,
+                locking: true,
+                ..Default::default()
+            },
+            MockProfile::Windows => CredentialCapabilities {
+                attributes: true,
+                binary_secrets: true,
+                empty_service: true,
+                empty_user: true,
+                max_target_len: Some(WIN_MAX_GENERIC_TARGET_NAME_LENGTH as usize),
+                max_user_len: Some(WIN_MAX_USERNAME_LENGTH as usize),
+                max_secret_len: Some(WIN_MAX_CREDENTIAL_BLOB_SIZE as usize),
+                ..Default::default()
+            },
+            MockProfile::MacOs | MockProfile::Ios => CredentialCapabilities {
+                binary_secrets: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Check a Windows credential's fields against the Credential Manager's limits.
+fn validate_windows_attributes(key: &MockKey, attributes: &HashMap<String, String>) -> Result<()> {
+    let len = |name: &str| attributes.get(name).map_or(0, String::len);
+    let target_name = key.target.as_deref().unwrap_or_default();
+    if len("username") > WIN_MAX_USERNAME_LENGTH as usize {
+        return Err(Error::TooLong("user".to_string(), WIN_MAX_USERNAME_LENGTH));
+    }
+    if target_name.is_empty() {
+        return Err(Error::Invalid(
+            "target".to_string(),
+            "cannot be empty".to_string(),
+        ));
+    }
+    if target_name.len() > WIN_MAX_GENERIC_TARGET_NAME_LENGTH as usize {
+        return Err(Error::TooLong(
+            "target".to_string(),
+            WIN_MAX_GENERIC_TARGET_NAME_LENGTH,
+        ));
+    }
+    if len("target_alias") > WIN_MAX_STRING_LENGTH as usize {
+        return Err(Error::TooLong(
+            "target alias".to_string(),
+            WIN_MAX_STRING_LENGTH,
+        ));
+    }
+    if len("comment") > WIN_MAX_STRING_LENGTH as usize {
+        return Err(Error::TooLong("comment".to_string(), WIN_MAX_STRING_LENGTH));
+    }
+    Ok(())
+}
+
+/// The data of a mock credential, shared with other credentials.
+type SharedData = Arc<Mutex<RefCell<MockData>>>;
+
+/// An in-memory store of mock credential data, shared by all the
+/// credentials built by its [credential_builder](MockStore::credential_builder).
+///
+/// Stores are cheap to clone: all the clones share the same data.
+/// See the module docs for details.
+#[derive(Debug, Clone, Default)]
+pub struct MockStore {
+    map: Arc<Mutex<HashMap<MockKey, SharedData>>>,
+    log: MockLog,
+    profile: MockProfile,
+}
+
+impl MockStore {
+    /// Make a new, empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make a new, empty store that emulates the given platform store.
+    ///
+    /// The store's builders build credentials with the given [profile](MockProfile),
+    /// and the store is keyed by the platform's credential identity.
+    /// For example, with the Windows profile, a [MockKey] has the
+    /// credential's target name as its target, and empty service and user.
+    pub fn with_profile(profile: MockProfile) -> Self {
+        Self {
+            profile,
+            ..Self::default()
+        }
+    }
+
+    /// Return a credential builder whose credentials keep their data in this store.
+    pub fn credential_builder(&self) -> Box<CredentialBuilder> {
+        Box::new(MockCredentialBuilder {
+            built: Default::default(),
+            store: Some(self.clone()),
+            log: self.log.clone(),
+            profile: self.profile,
+        })
+    }
+
+    /// The calls made on the builders for this store and their credentials.
+    pub fn calls(&self) -> MockCalls {
+        self.log.calls()
+    }
+
+    /// Forget the calls made on the builders for this store and their credentials.
+    pub fn clear_calls(&self) {
+        self.log.clear()
+    }
+
+    /// Set the secret for the given target, service, and user,
+    /// as if an entry for them had set it.
+    pub fn seed(&self, target: Option<&str>, service: &str, user: &str, secret: &[u8]) {
+        let data = self.data_for(self.profile.key(target, service, user));
+        let mut inner = data.lock().expect("Can't access mock data for seed");
+        let key = MockKey::new(target, service, user);
+        inner.get_mut().store_secret(self.profile, &key, secret);
+    }
+
+    /// Return a copy of the secret for every credential in the store that has one.
+    pub fn snapshot(&self) -> HashMap<MockKey, Vec<u8>> {
+        let map = self
+            .map
+            .lock()
+            .expect("Can't access mock store for snapshot");
+        let mut result = HashMap::new();
+        for (key, data) in map.iter() {
+            let inner = data.lock().expect("Can't access mock data for snapshot");
+            if let Some(secret) = &inner.borrow().secret {
+                result.insert(key.clone(), secret.expose_secret().to_vec());
+            }
+        }
+        result
+    }
+
+    /// Remove all secrets, pending errors, and recorded calls from the store.
+    ///
+    /// Existing entries for credentials in the store are not
+    /// invalidated; they will see the store as empty.
+    pub fn clear(&self) {
+        let map = self.map.lock().expect("Can't access mock store for clear");
+        for data in map.values() {
+            let mut inner = data.lock().expect("Can't access mock data for clear");
+            *inner.get_mut() = MockData::default();
+        }
+        self.log.clear();
+    }
+
+    /// Get the data for a key, adding it to the store if necessary.
+    fn data_for(&self, key: MockKey) -> SharedData {
+        let mut map = self.map.lock().expect("Can't access mock store for build");
+        map.entry(key).or_default().clone()
+    }
+
+    /// Make credentials for every key in the store.
+    fn credentials(&self) -> Vec<MockCredential> {
+        let map = self.map.lock().expect("Can't access mock store for search");
+        map.iter()
+            .map(|(key, data)| MockCredential {
+                inner: data.clone(),
+                target: key.target.clone(),
+                service: key.service.clone(),
+                user: key.user.clone(),
+                builder_log: Some(self.log.clone()),
+                profile: self.profile,
+            })
+            .collect()
+    }
+}
+
+/// The builder for mock credentials.
+///
+/// A builder made with [default_credential_builder] keeps track of the
+/// credentials it builds, for as long as some entry is using them,
+/// so it can find them in searches.  A builder made by a [MockStore]
+/// keeps its credentials' data in the store.
+///
+/// Builders keep a log of the calls made on them and on
+/// the credentials they build; see [calls](MockCredentialBuilder::calls).
+#[derive(Debug, Default)]
+pub struct MockCredentialBuilder {
+    built: Mutex<Vec<MockCredential>>,
+    store: Option<MockStore>,
+    log: MockLog,
+    profile: MockProfile,
+}
+
+impl MockCredentialBuilder {
+    /// The calls made on this builder and the credentials it has built.
+    ///
+    /// (For a builder made by a [MockStore], this includes the calls made
+    /// on the store's other builders and their credentials.)
+    pub fn calls(&self) -> MockCalls {
+        self.log.calls()
+    }
+
+    /// Forget the calls made on this builder and the credentials it has built.
+    pub fn clear_calls(&self) {
+        self.log.clear()
+    }
+
+    /// The platform store this builder's credentials emulate.
+    pub fn profile(&self) -> MockProfile {
+        self.profile
+    }
+}
+
+impl CredentialBuilderApi for MockCredentialBuilder {
+    /// Build a mock credential for the given target, service, and user.
+    ///
+    /// Since mocks don't persist between sessions,  all mocks
+    /// start off without passwords, unless they are in a shared store.
+    /// Builders with a platform [profile](MockProfile) reject
+    /// the values that platform's store rejects.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let mut credential = MockCredential::new_with_target(target, service, user)?;
+        credential.builder_log = Some(self.log.clone());
+        credential.profile = self.profile;
+        let valid = self.profile.validate(target, service, user);
+        let call = MockCall::new(MockOp::Build, Some(credential.key()), None, valid.is_ok());
+        self.log.push(call);
+        valid?;
+        if let Some(store) = &self.store {
+            credential.inner = store.data_for(self.profile.key(target, service, user));
+            return Ok(Box::new(credential));
+        }
+        let mut built = self
+            .built
+            .lock()
+            .expect("Can't access mock builder for build");
+        built.retain(MockCredential::is_shared);
+        built.push(credential.share());
+        Ok(Box::new(credential))
+    }
+
+    /// Find the credentials built by this builder that match the spec.
+    ///
+    /// Only credentials that have a password can match.  Builders
+    /// with a platform [profile](MockProfile) whose store can't search
+    /// return a [NotSupportedByStore](Error::NotSupportedByStore) error.
+    fn search(&self, spec: &HashMap<&str, &str>) -> Result<Vec<Box<Credential>>> {
+        let supported = self.profile.capabilities().search;
+        self.log
+            .push(MockCall::new(MockOp::Search, None, None, supported));
+        if !supported {
+            return Err(Error::NotSupportedByStore("search".to_string()));
+        }
+        if let Some(store) = &self.store {
+            let mut result: Vec<Box<Credential>> = vec![];
+            for credential in store.credentials() {
+                for item in credential
+                    .found_items()
+                    .into_iter()
+                    .filter(|c| c.matches(spec))
+                {
+                    result.push(Box::new(item))
+                }
+            }
+            return Ok(result);
+        }
+        let mut built = self
+            .built
+            .lock()
+            .expect("Can't access mock builder for search");
+        built.retain(MockCredential::is_shared);
+        let mut result: Vec<Box<Credential>> = vec![];
+        for credential in built.iter() {
+            for item in credential
+                .found_items()
+                .into_iter()
+                .filter(|c| c.matches(spec))
+            {
+                result.push(Box::new(item))
+            }
+        }
+        Ok(result)
+    }
+
+    /// Get an [Any][std::any::Any] reference to the mock credential builder.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// This keystore keeps the password in the entry,
+    /// unless it's using a shared store.
+    fn persistence(&self) -> CredentialPersistence {
+        match self.store {
+            Some(_) => CredentialPersistence::ProcessOnly,
+            None => CredentialPersistence::EntryOnly,
+        }
+    }
+
+    /// Mock credentials do conditional sets atomically.
+    /// (Builders with a platform [profile](MockProfile) report
+    /// whether that platform's store does.)
+    fn atomic_updates(&self) -> bool {
+        self.profile.capabilities().atomic_updates
+    }
+
+    /// Mocks accept any values, have attributes, and can search.
+    /// Builders with a platform [profile](MockProfile) report
+    /// the capabilities of that platform's store.
+    fn capabilities(&self) -> CredentialCapabilities {
+        self.profile.capabilities()
+    }
+}
+
+/// Return a mock credential builder for use by clients.
+pub fn default_credential_builder() -> Box<CredentialBuilder> {
+    Box::new(MockCredentialBuilder::default())
+}
+
+/// Return a mock credential builder that emulates the given platform store.
+///
+/// See [MockProfile] for details.
+pub fn profile_credential_builder(profile: MockProfile) -> Box<CredentialBuilder> {
+    Box::new(MockCredentialBuilder {
+        profile,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MockChaos, MockCredential, MockCredentialBuilder, MockKey, MockOp, MockProfile, MockStore,
+        default_credential_builder, profile_credential_builder,
+    };
+    use crate::credential::{CredentialPersistence, StoreStatus};
+    use crate::{Entry, Error, testing::generate_random_string};
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_persistence() {
+        assert!(matches!(
+            default_credential_builder().persistence(),
+            CredentialPersistence::EntryOnly
+        ))
+    }
+
+    #[test]
+    fn test_shared_store() {
+        let store = MockStore::new();
+        let builder = store.credential_builder();
+        assert!(matches!(
+            builder.persistence(),
+            CredentialPersistence::ProcessOnly
+        ));
+        let name = generate_random_string();
+        store.seed(Some("t"), &name, "user", b"seeded");
+        let entry1 = Entry::new_with_credential(builder.build(None, &name, "user").unwrap());
+        let entry2 = Entry::new_with_credential(builder.build(None, &name, "user").unwrap());
+        let entry3 = Entry::new_with_credential(builder.build(Some("t"), &name, "user").unwrap());
+        assert!(matches!(entry1.get_password(), Err(Error::NoEntry)));
+        assert_eq!(entry3.get_secret().unwrap(), b"seeded");
+        entry1.set_password("shared").unwrap();
+        assert_eq!(entry2.get_password().unwrap(), "shared");
+        let other = MockStore::new().credential_builder();
+        let entry4 = Entry::new_with_credential(other.build(None, &name, "user").unwrap());
+        assert!(matches!(entry4.get_password(), Err(Error::NoEntry)));
+        let snapshot = store.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[&MockKey::new(None, &name, "user")], b"shared");
+        let spec = HashMap::from([("service", name.as_str())]);
+        assert_eq!(builder.search(&spec).unwrap().len(), 2);
+        entry2.delete_credential().unwrap();
+        assert!(matches!(entry1.get_password(), Err(Error::NoEntry)));
+        store.clear();
+        assert!(store.snapshot().is_empty());
+        assert!(matches!(entry3.get_password(), Err(Error::NoEntry)));
+        entry3.set_password("after clear").unwrap();
+        assert_eq!(store.snapshot().len(), 1);
+    }
+
+    #[test]
+    fn test_call_log() {
+        let builder = default_credential_builder();
+        let mock_builder: &MockCredentialBuilder = builder.as_any().downcast_ref().unwrap();
+        let name = generate_random_string();
+        let entry1 = Entry::new_with_credential(builder.build(None, &name, "user1").unwrap());
+        let entry2 = Entry::new_with_credential(builder.build(Some("t"), &name, "user2").unwrap());
+        assert!(matches!(entry1.get_password(), Err(Error::NoEntry)));
+        entry1.set_password("first").unwrap();
+        entry2.set_secret(b"second").unwrap();
+        entry1.get_zeroizing_password().unwrap();
+        builder.search(&HashMap::new()).unwrap();
+        let mock: &MockCredential = entry1.get_credential().downcast_ref().unwrap();
+        let calls = mock.calls();
+        assert_eq!(
+            calls.ops(),
+            vec![
+                MockOp::GetPassword,
+                MockOp::SetPassword,
+                MockOp::GetZeroizingPassword
+            ]
+        );
+        assert!(!calls[0].succeeded && calls[1].succeeded);
+        assert!(calls[1].secret_matches(b"first"));
+        assert!(!calls[1].secret_matches(b"second"));
+        assert!(calls[1].time >= calls[0].time);
+        assert!(!format!("{calls:?}").contains("first"));
+        assert_eq!(calls[0].key, Some(MockKey::new(None, &name, "user1")));
+        let calls = mock_builder.calls();
+        calls.assert_count(MockOp::Build, 2);
+        calls.assert_count(MockOp::Search, 1);
+        calls.assert_count(MockOp::SetSecret, 1);
+        calls.assert_never(MockOp::DeleteCredential);
+        assert_eq!(calls.len(), 7);
+        assert_eq!(calls[4].key, Some(MockKey::new(Some("t"), &name, "user2")));
+        mock.clear_calls();
+        assert!(mock.calls().is_empty());
+        mock_builder.clear_calls();
+        assert!(mock_builder.calls().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected 0 DeleteCredential calls but there were 1")]
+    fn test_call_log_assertion() {
+        let store = MockStore::new();
+        let entry =
+            Entry::new_with_credential(store.credential_builder().build(None, "s", "u").unwrap());
+        _ = entry.delete_credential();
+        store.calls().assert_never(MockOp::DeleteCredential);
+    }
+
+    #[test]
+    fn test_scripted_errors() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        let unavailable = || Error::NoStorageAccess("scripted".into());
+        mock.script_errors(
+            MockOp::SetSecret,
+            std::iter::repeat_with(unavailable).take(2),
+        );
+        mock.script_errors(MockOp::GetSecret, [Error::TooLong("x".to_string(), 1)]);
+        entry.set_password("not scripted").unwrap();
+        for _ in 0..2 {
+            assert!(matches!(
+                entry.set_secret(b"fails"),
+                Err(Error::NoStorageAccess(_))
+            ));
+        }
+        entry.set_secret(b"works").unwrap();
+        mock.set_error(Error::NoEntry);
+        assert!(matches!(entry.get_secret(), Err(Error::NoEntry)));
+        assert!(matches!(entry.get_secret(), Err(Error::TooLong(_, 1))));
+        assert_eq!(entry.get_secret().unwrap(), b"works");
+        mock.calls().assert_count(MockOp::SetSecret, 3);
+    }
+
+    #[test]
+    fn test_chaos() {
+        let run = |seed: u64| {
+            let entry = entry_new("chaos", "chaos");
+            let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+            let chaos = MockChaos::new(0.5, seed, |op| {
+                Error::PlatformFailure(format!("chaos in {op:?}").into())
+            });
+            mock.set_chaos(Some(chaos));
+            let results: Vec<bool> = (0..200).map(|_| entry.set_password("p").is_ok()).collect();
+            mock.set_chaos(None);
+            entry.set_password("p").unwrap();
+            results
+        };
+        let results = run(42);
+        let failures = results.iter().filter(|ok| !**ok).count();
+        assert!((50..150).contains(&failures), "{failures} failures");
+        assert_eq!(results, run(42), "Chaos isn't reproducible");
+        assert_ne!(results, run(43), "Chaos ignores seed");
+        let entry = entry_new("chaos", "chaos");
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        mock.set_chaos(Some(MockChaos::new(1.0, 0, |_| Error::NoEntry)));
+        assert!(matches!(entry.set_password("p"), Err(Error::NoEntry)));
+        mock.set_chaos(Some(MockChaos::new(0.0, 0, |_| Error::NoEntry)));
+        entry.set_password("p").unwrap();
+    }
+
+    #[test]
+    fn test_latency() {
+        let entry = entry_new("latency", "latency");
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        let latency = Duration::from_millis(50);
+        mock.set_latency(MockOp::SetPassword, latency);
+        let start = Instant::now();
+        entry.set_password("slow").unwrap();
+        assert!(start.elapsed() >= latency);
+        // only lower bounds on elapsed time are reliable, so check
+        // the other operations' latencies rather than timing them
+        let latencies = |mock: &MockCredential| mock.inner.lock().unwrap().borrow().latency.clone();
+        assert_eq!(
+            latencies(mock),
+            HashMap::from([(MockOp::SetPassword, latency)])
+        );
+        mock.set_latency(MockOp::SetPassword, Duration::ZERO);
+        assert!(latencies(mock).is_empty());
+        entry.set_password("fast").unwrap();
+    }
+
+    #[test]
+    fn test_probe() {
+        let report = default_credential_builder().probe();
+        assert!(report.is_available(), "{report}");
+        let report = crate::with_credential_builder(
+            default_credential_builder(),
+            crate::probe_default_store,
+        );
+        assert_eq!(report.status, StoreStatus::Available, "{report}");
+    }
+
+    #[test]
+    fn test_conformance() {
+        crate::testing::run_conformance_suite(default_credential_builder().as_ref());
+        crate::testing::run_conformance_suite(MockStore::new().credential_builder().as_ref());
+        for profile in [
+            MockProfile::SecretService,
+            MockProfile::Windows,
+            MockProfile::MacOs,
+            MockProfile::Ios,
+        ] {
+            let store = MockStore::with_profile(profile);
+            crate::testing::run_conformance_suite(store.credential_builder().as_ref());
+        }
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = default_credential_builder().capabilities();
+        assert!(capabilities.search && capabilities.atomic_updates);
+        assert!(capabilities.attributes);
+        assert_eq!(capabilities.max_secret_len, None);
+    }
+
+    #[test]
+    fn test_windows_profile() {
+        let store = MockStore::with_profile(MockProfile::Windows);
+        let builder = store.credential_builder();
+        assert!(matches!(
+            builder.build(None, "service", &"u".repeat(514)),
+            Err(Error::TooLong(name, 513)) if name == "user"
+        ));
+        assert!(matches!(
+            builder.build(None, &"s".repeat(200), "user"),
+            Err(Error::TooLong(name, 256)) if name == "comment"
+        ));
+        assert!(matches!(
+            builder.build(Some(""), "service", "user"),
+            Err(Error::Invalid(_, _))
+        ));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        entry.set_password(&"p".repeat(1280)).unwrap();
+        assert!(matches!(
+            entry.set_password(&"p".repeat(1281)),
+            Err(Error::TooLong(_, 2560))
+        ));
+        assert!(matches!(
+            entry.set_secret(&[0; 2561]),
+            Err(Error::TooLong(_, 2560))
+        ));
+        // entries with the same target share a credential
+        let entry1 = Entry::new_with_credential(builder.build(Some("t"), "s1", "u1").unwrap());
+        let entry2 = Entry::new_with_credential(builder.build(Some("t"), "s2", "u2").unwrap());
+        entry1.set_password("shared").unwrap();
+        assert_eq!(entry2.get_password().unwrap(), "shared");
+        assert!(
+            store
+                .snapshot()
+                .contains_key(&MockKey::new(Some("user.service"), "", ""))
+        );
+        let attributes = entry1.get_attributes().unwrap();
+        assert_eq!(attributes.len(), 3);
+        assert_eq!(attributes["username"], "u1");
+        let update = HashMap::from([("comment", "new"), ("label", "ignored")]);
+        entry1.update_attributes(&update).unwrap();
+        assert_eq!(entry2.get_attributes().unwrap()["comment"], "new");
+        let long_alias = "a".repeat(257);
+        let update = HashMap::from([("target_alias", long_alias.as_str()), ("comment", "")]);
+        assert!(matches!(
+            entry1.update_attributes(&update),
+            Err(Error::TooLong(_, 256))
+        ));
+        assert_eq!(entry1.get_attributes().unwrap()["comment"], "new");
+        assert!(matches!(
+            builder.search(&HashMap::new()),
+            Err(Error::NotSupportedByStore(_))
+        ));
+        assert!(!builder.capabilities().search);
+        assert_eq!(builder.capabilities().max_secret_len, Some(2560));
+    }
+
+    #[test]
+    fn test_apple_profiles() {
+        for profile in [MockProfile::MacOs, MockProfile::Ios] {
+            let builder = profile_credential_builder(profile);
+            for (service, user) in [("", "user"), ("service", "")] {
+                assert!(matches!(
+                    builder.build(None, service, user),
+                    Err(Error::Invalid(_, _))
+                ));
+            }
+            let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+            entry.set_password("apple").unwrap();
+            assert!(entry.get_attributes().unwrap().is_empty());
+            entry
+                .update_attributes(&HashMap::from([("label", "")]))
+                .unwrap();
+            assert!(entry.get_attributes().unwrap().is_empty());
+            assert!(!builder.capabilities().attributes);
+        }
+        let builder = profile_credential_builder(MockProfile::Ios);
+        builder.build(Some("Default"), "service", "user").unwrap();
+        assert!(matches!(
+            builder.build(Some("other"), "service", "user"),
+            Err(Error::Invalid(_, _))
+        ));
+        // unknown macOS keychain names mean the user keychain
+        let store = MockStore::with_profile(MockProfile::MacOs);
+        let builder = store.credential_builder();
+        let entry1 = Entry::new_with_credential(builder.build(Some("nonsense"), "s", "u").unwrap());
+        let entry2 = Entry::new_with_credential(builder.build(Some("user"), "s", "u").unwrap());
+        entry1.set_password("keychain").unwrap();
+        assert_eq!(entry2.get_password().unwrap(), "keychain");
+    }
+
+    #[test]
+    fn test_secret_service_profile() {
+        let store = MockStore::with_profile(MockProfile::SecretService);
+        let builder = store.credential_builder();
+        assert!(matches!(
+            builder.build(Some(""), "service", "user"),
+            Err(Error::Invalid(_, _))
+        ));
+        let entry1 = Entry::new_with_credential(builder.build(None, "", "").unwrap());
+        let entry2 = Entry::new_with_credential(builder.build(Some("default"), "", "").unwrap());
+        entry1.set_password("default target").unwrap();
+        assert_eq!(entry2.get_password().unwrap(), "default target");
+        assert!(matches!(
+            entry1.update_attributes(&HashMap::from([("label", "")])),
+            Err(Error::Invalid(_, _))
+        ));
+        assert!(builder.capabilities().search);
+        assert!(!builder.atomic_updates());
+        let calls = store.calls();
+        assert!(!calls[0].succeeded);
+    }
+
+    fn entry_new(service: &str, user: &str) -> Entry {
+        let credential = MockCredential::new_with_target(None, service, user).unwrap();
+        Entry::new_with_credential(Box::new(credential))
+    }
+
+    #[test]
+    fn test_missing_entry() {
+        crate::testing::test_missing_entry(entry_new);
+    }
+
+    #[test]
+    fn test_empty_password() {
+        crate::testing::test_empty_password(entry_new);
+    }
+
+    #[test]
+    fn test_round_trip_ascii_password() {
+        crate::testing::test_round_trip_ascii_password(entry_new);
+    }
+
+    #[test]
+    fn test_round_trip_non_ascii_password() {
+        crate::testing::test_round_trip_non_ascii_password(entry_new);
+    }
+
+    #[test]
+    fn test_round_trip_random_secret() {
+        crate::testing::test_round_trip_random_secret(entry_new);
+    }
+
+    #[test]
+    fn test_update() {
+        crate::testing::test_update(entry_new);
+    }
+
+    #[test]
+    fn test_get_update_attributes() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        assert!(matches!(entry.get_attributes(), Err(Error::NoEntry)));
+        let mut in_map = HashMap::from([
+            ("label", "test label value"),
+            ("test attribute name", "test attribute value"),
+            ("target", "ignored target value"),
+            ("service", "ignored service value"),
+            ("username", "ignored username value"),
+        ]);
+        assert!(matches!(
+            entry.update_attributes(&in_map),
+            Err(Error::NoEntry)
+        ));
+        entry.set_password("test password for attributes").unwrap();
+        let out_map = entry.get_attributes().unwrap();
+        assert_eq!(out_map.len(), 2);
+        assert_eq!(out_map["application"], "rust-keyring");
+        assert!(out_map["label"].starts_with(&format!("{name}@{name}:default")));
+        entry.update_attributes(&in_map).unwrap();
+        let after_map = entry.get_attributes().unwrap();
+        assert_eq!(after_map.len(), 3);
+        assert_eq!(after_map["label"], in_map["label"]);
+        assert_eq!(after_map["test attribute name"], "test attribute value");
+        in_map.insert("label", "");
+        in_map.insert("test attribute name", "not updated");
+        assert!(matches!(
+            entry.update_attributes(&in_map),
+            Err(Error::Invalid(_, _))
+        ));
+        assert_eq!(entry.get_attributes().unwrap(), after_map);
+        entry
+            .set_password("update doesn't change attributes")
+            .unwrap();
+        assert_eq!(entry.get_attributes().unwrap(), after_map);
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.get_attributes(), Err(Error::NoEntry)));
+        entry.set_password("recreated").unwrap();
+        assert_eq!(entry.get_attributes().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_search_attributes() {
+        let builder = default_credential_builder();
+        let name = generate_random_string();
+        let entry1 = Entry::new_with_credential(builder.build(None, &name, "user1").unwrap());
+        let entry2 = Entry::new_with_credential(builder.build(None, &name, "user2").unwrap());
+        entry1.set_password("pw1").unwrap();
+        entry2.set_password("pw2").unwrap();
+        entry2
+            .update_attributes(&HashMap::from([("color", "blue"), ("label", "found")]))
+            .unwrap();
+        let spec = HashMap::from([("service", name.as_str()), ("color", "blue")]);
+        let found = builder.search(&spec).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get_password().unwrap(), "pw2");
+        let spec = HashMap::from([("service", name.as_str()), ("label", "found")]);
+        assert!(builder.search(&spec).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let store = MockStore::new();
+        let builder = store.credential_builder();
+        let name = generate_random_string();
+        let entry = Entry::new_with_credential(builder.build(None, &name, &name).unwrap());
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        mock.add_duplicate(b"duplicate");
+        // without a secret of its own, the credential isn't ambiguous
+        assert!(matches!(entry.get_secret(), Err(Error::NoEntry)));
+        store.seed(None, &name, &name, b"original");
+        let items = match entry.get_secret() {
+            Err(Error::Ambiguous(items)) => items,
+            other => panic!("Duplicate item isn't ambiguous: {other:?}"),
+        };
+        assert_eq!(items.len(), 2);
+        assert!(matches!(
+            entry.set_password("ambiguous"),
+            Err(Error::Ambiguous(_))
+        ));
+        let spec = HashMap::from([("service", name.as_str())]);
+        assert_eq!(builder.search(&spec).unwrap().len(), 2);
+        let duplicate = Entry::new_with_credential(items.into_iter().nth(1).unwrap());
+        assert_eq!(duplicate.get_secret().unwrap(), b"duplicate");
+        assert!(duplicate.get_attributes().unwrap().contains_key("label"));
+        duplicate.delete_credential().unwrap();
+        assert_eq!(entry.get_secret().unwrap(), b"original");
+        assert_eq!(builder.search(&spec).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_set_error() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        let password = "test ascii password";
+        let mock: &MockCredential = entry
+            .inner
+            .as_any()
+            .downcast_ref()
+            .expect("Downcast failed");
+        mock.set_error(Error::Invalid(
+            "mock error".to_string(),
             "is an error".to_string(),
         ));
         assert!(
@@ -332,4 +1967,292 @@ mod tests {
             "Able to read a deleted ascii password"
         )
     }
+
+    #[test]
+    fn test_search() {
+        let builder = default_credential_builder();
+        let name = generate_random_string();
+        let entry1 = Entry::new_with_credential(builder.build(None, &name, "user1").unwrap());
+        let entry2 = Entry::new_with_credential(builder.build(None, &name, "user2").unwrap());
+        let entry3 = Entry::new_with_credential(builder.build(Some("t"), &name, "user1").unwrap());
+        let spec = HashMap::from([("service", name.as_str())]);
+        assert!(
+            builder.search(&spec).unwrap().is_empty(),
+            "Found credentials without passwords"
+        );
+        entry1.set_password("pw1").unwrap();
+        entry2.set_password("pw2").unwrap();
+        entry3.set_password("pw3").unwrap();
+        assert_eq!(builder.search(&spec).unwrap().len(), 3);
+        let spec = HashMap::from([("service", name.as_str()), ("user", "user1")]);
+        assert_eq!(builder.search(&spec).unwrap().len(), 2);
+        let spec = HashMap::from([("target", "t"), ("user", "user1")]);
+        let found = builder.search(&spec).unwrap();
+        assert_eq!(found.len(), 1);
+        let found = Entry::new_with_credential(found.into_iter().next().unwrap());
+        assert_eq!(found.get_password().unwrap(), "pw3");
+        found.set_password("updated").unwrap();
+        assert_eq!(entry3.get_password().unwrap(), "updated");
+        let spec = HashMap::from([("service", name.as_str()), ("label", "none")]);
+        assert!(builder.search(&spec).unwrap().is_empty());
+        entry2.delete_credential().unwrap();
+        drop(entry1);
+        let spec = HashMap::from([("service", name.as_str())]);
+        assert_eq!(builder.search(&spec).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_get_secrets_many() {
+        let builder = default_credential_builder();
+        let name = generate_random_string();
+        let entry1 = Entry::new_with_credential(builder.build(None, &name, "user1").unwrap());
+        let entry2 = Entry::new_with_credential(builder.build(None, &name, "user2").unwrap());
+        let entry3 = Entry::new_with_credential(builder.build(None, &name, "user3").unwrap());
+        entry1.set_secret(b"secret1").unwrap();
+        entry3.set_secret(b"secret3").unwrap();
+        let results = Entry::get_secrets_many(&[&entry3, &entry2, &entry1]);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), b"secret3");
+        assert!(matches!(results[1], Err(Error::NoEntry)));
+        assert_eq!(results[2].as_ref().unwrap(), b"secret1");
+        assert!(Entry::get_secrets_many(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_get_secrets_many_uses_batch_builders() {
+        use crate::credential::{
+            Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+        };
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // a store that counts the batches it's asked for
+        #[derive(Debug)]
+        struct Batching(Box<Credential>, Arc<AtomicUsize>);
+        struct BatchingBuilder(Arc<AtomicUsize>);
+        impl CredentialApi for Batching {
+            fn set_password(&self, password: &str) -> crate::Result<()> {
+                self.0.set_password(password)
+            }
+            fn set_secret(&self, secret: &[u8]) -> crate::Result<()> {
+                self.0.set_secret(secret)
+            }
+            fn get_password(&self) -> crate::Result<String> {
+                self.0.get_password()
+            }
+            fn get_secret(&self) -> crate::Result<Vec<u8>> {
+                self.0.get_secret()
+            }
+            fn delete_credential(&self) -> crate::Result<()> {
+                self.0.delete_credential()
+            }
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+            fn batch_builder(&self) -> Option<Box<CredentialBuilder>> {
+                Some(Box::new(BatchingBuilder(self.1.clone())))
+            }
+        }
+        impl CredentialBuilderApi for BatchingBuilder {
+            fn build(&self, _: Option<&str>, _: &str, _: &str) -> crate::Result<Box<Credential>> {
+                unimplemented!()
+            }
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+            fn get_secrets_many(&self, credentials: &[&Credential]) -> Vec<crate::Result<Vec<u8>>> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                credentials.iter().map(|c| c.get_secret()).collect()
+            }
+        }
+
+        let batches = Arc::new(AtomicUsize::new(0));
+        let name = generate_random_string();
+        let batching = |user: &str| {
+            let mock = default_credential_builder()
+                .build(None, &name, user)
+                .unwrap();
+            Entry::new_with_credential(Box::new(Batching(mock, batches.clone())))
+        };
+        let entry1 = batching("user1");
+        let entry2 = Entry::new_with_credential(
+            default_credential_builder()
+                .build(None, &name, "user2")
+                .unwrap(),
+        );
+        let entry3 = batching("user3");
+        entry1.set_secret(b"secret1").unwrap();
+        entry2.set_secret(b"secret2").unwrap();
+        let results = Entry::get_secrets_many(&[&entry3, &entry2, &entry1]);
+        assert_eq!(
+            batches.load(Ordering::SeqCst),
+            1,
+            "Batching entries weren't batched"
+        );
+        assert!(matches!(results[0], Err(Error::NoEntry)));
+        assert_eq!(results[1].as_ref().unwrap(), b"secret2");
+        assert_eq!(results[2].as_ref().unwrap(), b"secret1");
+    }
+
+    #[test]
+    fn test_lock_and_unlock() {
+        let store = MockStore::new();
+        let builder = store.credential_builder();
+        let entry1 = Entry::new_with_credential(builder.build(None, "lock", "user").unwrap());
+        let entry2 = Entry::new_with_credential(builder.build(None, "lock", "user").unwrap());
+        entry1.set_password("locked").unwrap();
+        assert!(!entry1.is_locked().unwrap());
+        entry1.lock().unwrap();
+        assert!(entry2.is_locked().unwrap());
+        assert!(matches!(entry2.get_password(), Err(Error::Locked)));
+        assert!(matches!(entry2.set_password("x"), Err(Error::Locked)));
+        entry2.unlock().unwrap();
+        assert!(!entry1.is_locked().unwrap());
+        assert_eq!(entry1.get_password().unwrap(), "locked");
+        let calls = store.calls();
+        calls.assert_count(MockOp::Lock, 1);
+        calls.assert_count(MockOp::Unlock, 1);
+        calls.assert_count(MockOp::IsLocked, 3);
+        let store = MockStore::with_profile(MockProfile::Windows);
+        let builder = store.credential_builder();
+        let entry = Entry::new_with_credential(builder.build(None, "lock", "user").unwrap());
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        mock.script_errors(MockOp::Lock, vec![Error::NoEntry]);
+        assert!(matches!(entry.lock(), Err(Error::NoEntry)));
+        assert!(matches!(entry.lock(), Err(Error::NotSupportedByStore(_))));
+        store.calls().assert_count(MockOp::Lock, 2);
+        assert!(!entry.is_locked().unwrap());
+        entry.unlock().unwrap();
+        assert!(!builder.capabilities().locking);
+    }
+
+    #[test]
+    fn test_named_store() {
+        let store = generate_random_string();
+        let name = generate_random_string();
+        assert!(matches!(
+            Entry::new_in(&store, &name, &name),
+            Err(Error::Invalid(_, _))
+        ));
+        crate::register_credential_builder(&store, default_credential_builder());
+        let entry = Entry::new_in(&store, &name, &name).expect("Can't create entry in store");
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        assert_eq!(mock.target, None);
+        entry.set_password("in store").unwrap();
+        let spec = HashMap::from([("service", name.as_str())]);
+        let found = Entry::search_in(&store, &spec).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get_password().unwrap(), "in store");
+        let entry = Entry::new_with_target_in(&store, "t", &name, &name).unwrap();
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        assert_eq!(mock.target.as_deref(), Some("t"));
+        assert!(crate::unregister_credential_builder(&store));
+        assert!(!crate::unregister_credential_builder(&store));
+        assert!(matches!(
+            Entry::search_in(&store, &spec),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_scoped_builder() {
+        let name = generate_random_string();
+        let spec = HashMap::from([("service", name.as_str())]);
+        crate::with_credential_builder(default_credential_builder(), || {
+            let outer = Entry::new(&name, "outer").expect("Can't create scoped entry");
+            assert!(
+                outer
+                    .get_credential()
+                    .downcast_ref::<MockCredential>()
+                    .is_some()
+            );
+            outer.set_password("outer").unwrap();
+            {
+                let _guard = crate::scoped_credential_builder(default_credential_builder());
+                let inner = Entry::new(&name, "inner").unwrap();
+                inner.set_password("inner").unwrap();
+                let found = Entry::search(&spec).unwrap();
+                assert_eq!(found.len(), 1);
+                assert_eq!(found[0].get_password().unwrap(), "inner");
+            }
+            let found = Entry::search(&spec).unwrap();
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].get_password().unwrap(), "outer");
+            let mock_capabilities = default_credential_builder().capabilities();
+            assert_eq!(crate::default_credential_capabilities(), mock_capabilities);
+            std::thread::spawn(move || {
+                assert_ne!(crate::default_credential_capabilities(), mock_capabilities)
+            })
+            .join()
+            .expect("Scoped builder leaked to another thread");
+        });
+    }
+
+    #[test]
+    fn test_zeroizing_password() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        assert!(matches!(
+            entry.get_zeroizing_password(),
+            Err(Error::NoEntry)
+        ));
+        entry.set_password("zeroizing").unwrap();
+        let password = entry.get_zeroizing_password().unwrap();
+        assert_eq!(password.expose_secret(), "zeroizing");
+        assert_eq!(
+            entry.get_zeroizing_secret().unwrap().expose_secret(),
+            b"zeroizing"
+        );
+        entry.set_secret(b"\xff").unwrap();
+        assert!(matches!(
+            entry.get_zeroizing_password(),
+            Err(Error::BadEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_set_secret_if() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        assert!(matches!(
+            entry.set_secret_if(Some(b"old"), b"new"),
+            Err(Error::Conflict)
+        ));
+        entry.set_secret_if(None, b"old").unwrap();
+        assert!(matches!(
+            entry.set_secret_if(None, b"new"),
+            Err(Error::Conflict)
+        ));
+        entry.set_secret_if(Some(b"old"), b"new").unwrap();
+        assert_eq!(entry.get_secret().unwrap(), b"new");
+    }
+
+    #[test]
+    fn test_update_secret() {
+        let name = generate_random_string();
+        let entry = std::sync::Arc::new(entry_new(&name, &name));
+        let mut handles = vec![];
+        for _ in 0..10 {
+            let entry = entry.clone();
+            handles.push(std::thread::spawn(move || {
+                for _ in 0..10 {
+                    // under heavy contention an update can give up, but it must never lose
+                    loop {
+                        match entry.update_secret(|old| match old {
+                            None => vec![1],
+                            Some(old) => vec![old[0] + 1],
+                        }) {
+                            Ok(()) => break,
+                            Err(Error::Conflict) => continue,
+                            Err(err) => panic!("Can't update secret: {err:?}"),
+                        }
+                    }
+                }
+            }))
+        }
+        for handle in handles {
+            handle.join().expect("Couldn't execute on thread")
+        }
+        assert_eq!(entry.get_secret().unwrap(), vec![100]);
+    }
 }
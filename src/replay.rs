@@ -0,0 +1,1051 @@
+/*!
+
+# Recording and replaying credential store interactions
+
+A [RecordingCredentialBuilder] wraps any other credential builder.
+It (and the credentials it builds) pass every call through to the wrapped
+builder (and its credentials), and write each call and its result
+(including errors) to a file.  A [ReplayCredentialBuilder] reads such a file and
+serves the recorded results back, without using any credential store at all.
+This lets you capture the behavior of a real store once (for example, on a
+machine with a secret service running) and then test against it anywhere.
+
+For example, here's a recording of calls on the mock store, and its replay:
+```rust
+# use keyring::{Entry, mock, replay::{RecordingCredentialBuilder, ReplayCredentialBuilder}};
+let path = std::env::temp_dir().join("keyring-replay-example.txt");
+let builder = RecordingCredentialBuilder::new(mock::default_credential_builder(), &path).unwrap();
+keyring::set_default_credential_builder(Box::new(builder));
+let entry = Entry::new("service", "user").unwrap();
+entry.set_password("recorded").unwrap();
+assert!(entry.get_attributes().is_ok());
+// now replay what happened
+let builder = ReplayCredentialBuilder::from_file(&path).unwrap();
+keyring::set_default_credential_builder(Box::new(builder));
+let entry = Entry::new("service", "user").unwrap();
+entry.set_password("recorded").unwrap();
+assert!(entry.get_attributes().is_ok());
+# std::fs::remove_file(&path).unwrap();
+```
+
+The replay has to make the same calls, with the same arguments, in the same
+order as the recording.  As soon as a call diverges from the recording
+(or a call is made after the recording runs out), the replay panics
+with a message that shows both the recorded call and the actual one.  Use
+[assert_finished](ReplayCredentialBuilder::assert_finished) at the end of a
+test to check that every recorded call was made.
+
+Recordings include the builder calls `build`, `search`, and `probe`,
+and every call on a credential.  They don't include calls that describe the
+builder, such as `persistence` and `capabilities`: a replay builder reports
+the default values for those.  The errors in a recording keep their variant
+and their values, except for the platform-specific errors inside
+[PlatformFailure](Error::PlatformFailure) and
+[NoStorageAccess](Error::NoStorageAccess) errors, which are replayed as
+strings holding the platform error's message.  The credentials in
+[Ambiguous](Error::Ambiguous) errors are recorded (and replayed) like
+those returned by `build` and `search`.
+
+_N.B._ Recordings contain the passwords and secrets that were set and retrieved,
+so only record interactions with test credentials.
+
+## File format
+
+Recordings are text files with one call per line, so they can be
+reviewed and edited by hand.  Lines that start with `#` are comments.
+Each call line has the subject of the call (`builder`, or the number of a
+credential), the call name and its arguments, then `->` and the result,
+which is either `ok` followed by the returned values or `err` followed by the
+error variant and its values.  Strings and byte strings are written with a
+leading `"`, and with spaces, `%`, and non-printing bytes written as `%` and two hex
+digits; `-` stands for `None`.  For example:
+```text
+builder build - "service "user -> ok 0
+0 set_password "recorded -> ok
+0 get_password -> ok "recorded
+0 delete_credential -> ok
+0 delete_credential -> err NoEntry
+```
+ */
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use log::warn;
+
+use super::credential::{
+    Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi, CredentialCapabilities,
+    CredentialPersistence, ProbeReport, StoreStatus,
+};
+use super::error::{Error, Result};
+use super::secret::{SecretBytes, SecretString};
+
+/// The first line of every recording.
+const HEADER: &str = "# keyring-rs recording (format 1)";
+
+/// The shared state of a recording builder and its credentials.
+struct Recorder {
+    state: Mutex<RecorderState>,
+}
+
+struct RecorderState {
+    out: Box<dyn Write + Send>,
+    next_id: usize,
+}
+
+impl Recorder {
+    /// Wrap a credential so its calls are recorded, giving it a new id.
+    fn wrap(self: &Arc<Self>, inner: Box<Credential>) -> RecordingCredential {
+        let mut state = self.state.lock().expect("Can't access recorder");
+        let id = state.next_id;
+        state.next_id += 1;
+        RecordingCredential {
+            inner,
+            id,
+            recorder: self.clone(),
+        }
+    }
+
+    /// Write a call and its result to the recording.
+    ///
+    /// Since recording calls can't fail, write errors are logged.
+    fn write(&self, subject: &str, call: &str, outcome: &str) {
+        let mut state = self.state.lock().expect("Can't access recorder");
+        let result =
+            writeln!(state.out, "{subject} {call} -> {outcome}").and_then(|()| state.out.flush());
+        if let Err(err) = result {
+            warn!("couldn't write call to recording: {err}");
+        }
+    }
+
+    /// Record a call and its result, returning the result.
+    ///
+    /// The credentials in [Ambiguous](Error::Ambiguous) errors are
+    /// wrapped so that calls on them are also recorded.
+    fn record<T>(
+        self: &Arc<Self>,
+        subject: &str,
+        call: &str,
+        result: Result<T>,
+        ok: impl FnOnce(&T) -> String,
+    ) -> Result<T> {
+        let result = result.map_err(|err| match err {
+            Error::Ambiguous(creds) => Error::Ambiguous(
+                creds
+                    .into_iter()
+                    .map(|cred| Box::new(self.wrap(cred)) as Box<Credential>)
+                    .collect(),
+            ),
+            err => err,
+        });
+        let outcome = match &result {
+            Ok(value) => format!("ok {}", ok(value)),
+            Err(err) => format!("err {}", encode_error(err)),
+        };
+        self.write(subject, call, outcome.trim_end());
+        result
+    }
+}
+
+/// A credential builder that records the calls made on it and its credentials.
+///
+/// See the module docs for details.
+pub struct RecordingCredentialBuilder {
+    inner: Box<CredentialBuilder>,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingCredentialBuilder {
+    /// Record the calls made on the given builder in a (new) file at the given path.
+    ///
+    /// Returns a [PlatformFailure](Error::PlatformFailure) error if the
+    /// file can't be created.
+    pub fn new(inner: Box<CredentialBuilder>, path: impl AsRef<Path>) -> Result<Self> {
+        let file =
+            std::fs::File::create(path).map_err(|err| Error::PlatformFailure(Box::new(err)))?;
+        Self::with_writer(inner, Box::new(file))
+    }
+
+    /// Record the calls made on the given builder to the given writer.
+    ///
+    /// The writer is flushed after each call is recorded.
+    pub fn with_writer(
+        inner: Box<CredentialBuilder>,
+        mut out: Box<dyn Write + Send>,
+    ) -> Result<Self> {
+        writeln!(out, "{HEADER}").map_err(|err| Error::PlatformFailure(Box::new(err)))?;
+        Ok(Self {
+            inner,
+            recorder: Arc::new(Recorder {
+                state: Mutex::new(RecorderState { out, next_id: 0 }),
+            }),
+        })
+    }
+
+    /// The builder whose calls are being recorded.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+}
+
+impl std::fmt::Debug for RecordingCredentialBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordingCredentialBuilder")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialBuilderApi for RecordingCredentialBuilder {
+    /// Build a credential with the wrapped builder, and record the call.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let call = format!(
+            "build {} {} {}",
+            encode_option(target.map(str::as_bytes)),
+            encode_bytes(service.as_bytes()),
+            encode_bytes(user.as_bytes()),
+        );
+        let result = self
+            .inner
+            .build(target, service, user)
+            .map(|cred| Box::new(self.recorder.wrap(cred)) as Box<Credential>);
+        self.recorder.record("builder", &call, result, |cred| {
+            credential_id(cred.as_ref()).to_string()
+        })
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [RecordingCredentialBuilder].
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped builder.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search with the wrapped builder, and record the call.
+    fn search(&self, spec: &HashMap<&str, &str>) -> Result<Vec<Box<Credential>>> {
+        let call = format!("search {}", encode_map(spec));
+        let result = self.inner.search(spec).map(|creds| {
+            creds
+                .into_iter()
+                .map(|cred| Box::new(self.recorder.wrap(cred)) as Box<Credential>)
+                .collect::<Vec<_>>()
+        });
+        self.recorder.record("builder", &call, result, |creds| {
+            encode_ids(creds.iter().map(|cred| credential_id(cred.as_ref())))
+        })
+    }
+
+    /// Whether the wrapped builder does atomic updates.
+    fn atomic_updates(&self) -> bool {
+        self.inner.atomic_updates()
+    }
+
+    /// The capabilities of the wrapped builder.
+    fn capabilities(&self) -> CredentialCapabilities {
+        self.inner.capabilities()
+    }
+
+    /// Probe the wrapped builder, and record the call.
+    fn probe(&self) -> ProbeReport {
+        let report = self.inner.probe();
+        let outcome = format!(
+            "ok {:?} {}",
+            report.status,
+            encode_bytes(report.message.as_bytes())
+        );
+        self.recorder.write("builder", "probe", &outcome);
+        report
+    }
+}
+
+/// The id of a credential made by a recording builder.
+fn credential_id(cred: &Credential) -> usize {
+    cred.as_any()
+        .downcast_ref::<RecordingCredential>()
+        .expect("Recorded credential has the wrong type")
+        .id
+}
+
+/// A credential whose calls are recorded.
+///
+/// These are made by a [RecordingCredentialBuilder].
+pub struct RecordingCredential {
+    inner: Box<Credential>,
+    id: usize,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingCredential {
+    /// The credential whose calls are being recorded.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+
+    /// The number that identifies this credential in the recording.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Record a call on this credential and its result.
+    fn record<T>(&self, call: &str, result: Result<T>, ok: impl FnOnce(&T) -> String) -> Result<T> {
+        self.recorder.record(&self.id.to_string(), call, result, ok)
+    }
+}
+
+impl std::fmt::Debug for RecordingCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordingCredential")
+            .field("id", &self.id)
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialApi for RecordingCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        let call = format!("set_password {}", encode_bytes(password.as_bytes()));
+        self.record(&call, self.inner.set_password(password), |_| String::new())
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let call = format!("set_secret {}", encode_bytes(secret));
+        self.record(&call, self.inner.set_secret(secret), |_| String::new())
+    }
+
+    fn set_secret_if(&self, expected: Option<&[u8]>, secret: &[u8]) -> Result<()> {
+        let call = format!(
+            "set_secret_if {} {}",
+            encode_option(expected),
+            encode_bytes(secret)
+        );
+        let result = self.inner.set_secret_if(expected, secret);
+        self.record(&call, result, |_| String::new())
+    }
+
+    fn get_password(&self) -> Result<String> {
+        self.record("get_password", self.inner.get_password(), |password| {
+            encode_bytes(password.as_bytes())
+        })
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.record("get_secret", self.inner.get_secret(), |secret| {
+            encode_bytes(secret)
+        })
+    }
+
+    fn get_zeroizing_password(&self) -> Result<SecretString> {
+        let result = self.inner.get_zeroizing_password();
+        self.record("get_zeroizing_password", result, |password| {
+            encode_bytes(password.expose_secret().as_bytes())
+        })
+    }
+
+    fn get_zeroizing_secret(&self) -> Result<SecretBytes> {
+        let result = self.inner.get_zeroizing_secret();
+        self.record("get_zeroizing_secret", result, |secret| {
+            encode_bytes(secret.expose_secret())
+        })
+    }
+
+    fn get_attributes(&self) -> Result<HashMap<String, String>> {
+        self.record("get_attributes", self.inner.get_attributes(), |attrs| {
+            encode_map(attrs)
+        })
+    }
+
+    fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
+        let call = format!("update_attributes {}", encode_map(attributes));
+        let result = self.inner.update_attributes(attributes);
+        self.record(&call, result, |_| String::new())
+    }
+
+    fn is_locked(&self) -> Result<bool> {
+        let result = self.inner.is_locked();
+        self.record("is_locked", result, |locked| locked.to_string())
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.record("unlock", self.inner.unlock(), |_| String::new())
+    }
+
+    fn lock(&self) -> Result<()> {
+        self.record("lock", self.inner.lock(), |_| String::new())
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        let result = self.inner.delete_credential();
+        self.record("delete_credential", result, |_| String::new())
+    }
+
+    /// Return this credential wrapped in the [Any](std::any::Any) trait,
+    /// so it can be downcast.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A recorded call, with its (tokenized) result.
+#[derive(Debug)]
+struct Record {
+    line: usize,
+    call: String,
+    outcome: Vec<Token>,
+}
+
+/// The calls remaining in a replay.
+#[derive(Debug)]
+struct Replay {
+    records: Mutex<VecDeque<Record>>,
+}
+
+impl Replay {
+    /// Take the next recorded call, which must be the given call.
+    ///
+    /// # Panics
+    ///
+    /// If the next recorded call is not the given call, or there is none.
+    fn next(&self, subject: &str, call: &str) -> Tokens {
+        let call = format!("{subject} {call}");
+        let mut records = self.records.lock().expect("Can't access replay");
+        match records.pop_front() {
+            Some(record) if record.call == call => Tokens {
+                line: record.line,
+                tokens: record.outcome.into(),
+            },
+            Some(record) => panic!(
+                "Replay diverged at line {}: the recorded call is `{}` but the call made was `{call}`",
+                record.line, record.call
+            ),
+            None => panic!("Replay diverged: the call `{call}` was made after the recording ended"),
+        }
+    }
+}
+
+/// A credential builder that replays a recording.
+///
+/// See the module docs for details.
+#[derive(Debug)]
+pub struct ReplayCredentialBuilder {
+    replay: Arc<Replay>,
+}
+
+impl ReplayCredentialBuilder {
+    /// Replay the recording in the file at the given path.
+    ///
+    /// Returns a [PlatformFailure](Error::PlatformFailure) error if the file
+    /// can't be read, and an [Invalid](Error::Invalid) error if it isn't a recording.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let text =
+            std::fs::read_to_string(path).map_err(|err| Error::PlatformFailure(Box::new(err)))?;
+        Self::from_recording(&text)
+    }
+
+    /// Replay the given recording.
+    ///
+    /// Returns an [Invalid](Error::Invalid) error if the text isn't a recording.
+    pub fn from_recording(text: &str) -> Result<Self> {
+        let mut records = VecDeque::new();
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((call, outcome)) = line.split_once(" -> ") else {
+                return Err(Error::Invalid(
+                    "recording".to_string(),
+                    format!("line {line_number} has no result"),
+                ));
+            };
+            let outcome = outcome
+                .split_whitespace()
+                .map(|token| Token::parse(token, line_number))
+                .collect::<Result<_>>()?;
+            records.push_back(Record {
+                line: line_number,
+                call: call.to_string(),
+                outcome,
+            });
+        }
+        Ok(Self {
+            replay: Arc::new(Replay {
+                records: Mutex::new(records),
+            }),
+        })
+    }
+
+    /// The number of recorded calls that haven't been made yet.
+    pub fn remaining(&self) -> usize {
+        self.replay
+            .records
+            .lock()
+            .expect("Can't access replay")
+            .len()
+    }
+
+    /// Check that every recorded call has been made.
+    ///
+    /// # Panics
+    ///
+    /// If there are recorded calls that haven't been made.
+    #[track_caller]
+    pub fn assert_finished(&self) {
+        let records = self.replay.records.lock().expect("Can't access replay");
+        if let Some(record) = records.front() {
+            panic!(
+                "Replay isn't finished: {} recorded calls were not made, starting with `{}` at line {}",
+                records.len(),
+                record.call,
+                record.line
+            );
+        }
+    }
+
+    /// Make a credential with the given id.
+    fn credential(&self, id: usize) -> Box<Credential> {
+        Box::new(ReplayCredential {
+            id,
+            replay: self.replay.clone(),
+        })
+    }
+}
+
+impl CredentialBuilderApi for ReplayCredentialBuilder {
+    /// Replay a `build` call.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let call = format!(
+            "build {} {} {}",
+            encode_option(target.map(str::as_bytes)),
+            encode_bytes(service.as_bytes()),
+            encode_bytes(user.as_bytes()),
+        );
+        let mut outcome = self.replay.next("builder", &call);
+        outcome.result(&self.replay, |tokens| self.credential(tokens.number()))
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [ReplayCredentialBuilder].
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Replay a `search` call.
+    fn search(&self, spec: &HashMap<&str, &str>) -> Result<Vec<Box<Credential>>> {
+        let call = format!("search {}", encode_map(spec));
+        let mut outcome = self.replay.next("builder", &call);
+        outcome.result(&self.replay, |tokens| {
+            let mut creds = vec![];
+            while !tokens.is_empty() {
+                creds.push(self.credential(tokens.number()));
+            }
+            creds
+        })
+    }
+
+    /// Replay a `probe` call.
+    fn probe(&self) -> ProbeReport {
+        let mut outcome = self.replay.next("builder", "probe");
+        outcome.word("ok");
+        let status = match outcome.next_word().as_str() {
+            "Available" => StoreStatus::Available,
+            "Locked" => StoreStatus::Locked,
+            "NoDefaultStorage" => StoreStatus::NoDefaultStorage,
+            "Inaccessible" => StoreStatus::Inaccessible,
+            "Unreachable" => StoreStatus::Unreachable,
+            other => outcome.bad(&format!("unknown store status {other}")),
+        };
+        let message = outcome.string();
+        ProbeReport::new(status, &message)
+    }
+}
+
+/// A credential that replays a recording.
+///
+/// These are made by a [ReplayCredentialBuilder].
+#[derive(Debug)]
+pub struct ReplayCredential {
+    id: usize,
+    replay: Arc<Replay>,
+}
+
+impl ReplayCredential {
+    /// The number that identifies this credential in the recording.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Replay a call on this credential.
+    fn replay<T>(&self, call: &str, ok: impl FnOnce(&mut Tokens) -> T) -> Result<T> {
+        let mut outcome = self.replay.next(&self.id.to_string(), call);
+        outcome.result(&self.replay, ok)
+    }
+}
+
+impl CredentialApi for ReplayCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        let call = format!("set_password {}", encode_bytes(password.as_bytes()));
+        self.replay(&call, |_| ())
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.replay(&format!("set_secret {}", encode_bytes(secret)), |_| ())
+    }
+
+    fn set_secret_if(&self, expected: Option<&[u8]>, secret: &[u8]) -> Result<()> {
+        let call = format!(
+            "set_secret_if {} {}",
+            encode_option(expected),
+            encode_bytes(secret)
+        );
+        self.replay(&call, |_| ())
+    }
+
+    fn get_password(&self) -> Result<String> {
+        self.replay("get_password", Tokens::string)
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.replay("get_secret", Tokens::bytes)
+    }
+
+    fn get_zeroizing_password(&self) -> Result<SecretString> {
+        self.replay("get_zeroizing_password", |tokens| {
+            SecretString::new(tokens.string())
+        })
+    }
+
+    fn get_zeroizing_secret(&self) -> Result<SecretBytes> {
+        self.replay("get_zeroizing_secret", |tokens| {
+            SecretBytes::new(tokens.bytes())
+        })
+    }
+
+    fn get_attributes(&self) -> Result<HashMap<String, String>> {
+        self.replay("get_attributes", |tokens| {
+            let mut attributes = HashMap::new();
+            while !tokens.is_empty() {
+                attributes.insert(tokens.string(), tokens.string());
+            }
+            attributes
+        })
+    }
+
+    fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
+        self.replay(
+            &format!("update_attributes {}", encode_map(attributes)),
+            |_| (),
+        )
+    }
+
+    fn is_locked(&self) -> Result<bool> {
+        self.replay("is_locked", |tokens| tokens.next_word() == "true")
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.replay("unlock", |_| ())
+    }
+
+    fn lock(&self) -> Result<()> {
+        self.replay("lock", |_| ())
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.replay("delete_credential", |_| ())
+    }
+
+    /// Return this credential wrapped in the [Any](std::any::Any) trait,
+    /// so it can be downcast.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A token in the result of a recorded call.
+#[derive(Debug)]
+enum Token {
+    Word(String),
+    Value(Vec<u8>),
+    None,
+}
+
+impl Token {
+    fn parse(token: &str, line: usize) -> Result<Self> {
+        match token.strip_prefix('"') {
+            Some(value) => decode_bytes(value).map(Token::Value).ok_or_else(|| {
+                Error::Invalid(
+                    "recording".to_string(),
+                    format!("line {line} has a badly escaped value"),
+                )
+            }),
+            None if token == "-" => Ok(Token::None),
+            None => Ok(Token::Word(token.to_string())),
+        }
+    }
+}
+
+/// The tokens of a recorded result, being interpreted.
+///
+/// Since a recording that doesn't match the calls made is a divergence,
+/// these panic if the tokens aren't the expected ones.
+struct Tokens {
+    line: usize,
+    tokens: VecDeque<Token>,
+}
+
+impl Tokens {
+    fn bad(&self, problem: &str) -> ! {
+        panic!("Replay failed: the result at line {} {problem}", self.line)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    fn next_word(&mut self) -> String {
+        match self.tokens.pop_front() {
+            Some(Token::Word(word)) => word,
+            other => self.bad(&format!("has {other:?} where a word was expected")),
+        }
+    }
+
+    fn word(&mut self, expected: &str) {
+        let word = self.next_word();
+        if word != expected {
+            self.bad(&format!("has {word} where {expected} was expected"))
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> T {
+        let word = self.next_word();
+        word.parse()
+            .unwrap_or_else(|_| self.bad(&format!("has {word} where a number was expected")))
+    }
+
+    fn bytes(&mut self) -> Vec<u8> {
+        match self.tokens.pop_front() {
+            Some(Token::Value(bytes)) => bytes,
+            other => self.bad(&format!("has {other:?} where a value was expected")),
+        }
+    }
+
+    fn string(&mut self) -> String {
+        String::from_utf8(self.bytes()).unwrap_or_else(|_| self.bad("has a non-UTF-8 string"))
+    }
+
+    /// Interpret the tokens as a result, using the given function for a successful one.
+    fn result<T>(&mut self, replay: &Arc<Replay>, ok: impl FnOnce(&mut Tokens) -> T) -> Result<T> {
+        let result = match self.next_word().as_str() {
+            "ok" => Ok(ok(self)),
+            "err" => Err(self.error(replay)),
+            other => self.bad(&format!("starts with {other} instead of ok or err")),
+        };
+        if !self.is_empty() {
+            self.bad("has extra values")
+        }
+        result
+    }
+
+    fn error(&mut self, replay: &Arc<Replay>) -> Error {
+        match self.next_word().as_str() {
+            "PlatformFailure" => Error::PlatformFailure(self.string().into()),
+            "NoStorageAccess" => Error::NoStorageAccess(self.string().into()),
+            "NoEntry" => Error::NoEntry,
+            "BadEncoding" => Error::BadEncoding(self.bytes()),
+            "TooLong" => Error::TooLong(self.string(), self.number()),
+            "Invalid" => Error::Invalid(self.string(), self.string()),
+            "Ambiguous" => {
+                let mut creds: Vec<Box<Credential>> = vec![];
+                while !self.is_empty() {
+                    creds.push(Box::new(ReplayCredential {
+                        id: self.number(),
+                        replay: replay.clone(),
+                    }));
+                }
+                Error::Ambiguous(creds)
+            }
+            "NoDefaultCredentialBuilder" => Error::NoDefaultCredentialBuilder,
+            "NotSupportedByStore" => Error::NotSupportedByStore(self.string()),
+            "Conflict" => Error::Conflict,
+            "Locked" => Error::Locked,
+            other => self.bad(&format!("has unknown error {other}")),
+        }
+    }
+}
+
+/// Encode an error (and its values) for a recording.
+fn encode_error(err: &Error) -> String {
+    match err {
+        Error::PlatformFailure(err) => {
+            format!(
+                "PlatformFailure {}",
+                encode_bytes(err.to_string().as_bytes())
+            )
+        }
+        Error::NoStorageAccess(err) => {
+            format!(
+                "NoStorageAccess {}",
+                encode_bytes(err.to_string().as_bytes())
+            )
+        }
+        Error::NoEntry => "NoEntry".to_string(),
+        Error::BadEncoding(bytes) => format!("BadEncoding {}", encode_bytes(bytes)),
+        Error::TooLong(name, len) => format!("TooLong {} {len}", encode_bytes(name.as_bytes())),
+        Error::Invalid(name, reason) => format!(
+            "Invalid {} {}",
+            encode_bytes(name.as_bytes()),
+            encode_bytes(reason.as_bytes())
+        ),
+        Error::Ambiguous(creds) => format!(
+            "Ambiguous {}",
+            encode_ids(creds.iter().map(|cred| credential_id(cred.as_ref())))
+        ),
+        Error::NoDefaultCredentialBuilder => "NoDefaultCredentialBuilder".to_string(),
+        Error::NotSupportedByStore(op) => {
+            format!("NotSupportedByStore {}", encode_bytes(op.as_bytes()))
+        }
+        Error::Conflict => "Conflict".to_string(),
+        Error::Locked => "Locked".to_string(),
+    }
+}
+
+/// Encode a string or byte string as a single token.
+fn encode_bytes(bytes: &[u8]) -> String {
+    let mut result = String::from("\"");
+    for &b in bytes {
+        if b.is_ascii_graphic() && b != b'%' {
+            result.push(b as char);
+        } else {
+            write!(result, "%{b:02x}").expect("Can't write to string");
+        }
+    }
+    result
+}
+
+/// Decode a token (without its leading quote) made by [encode_bytes].
+fn decode_bytes(token: &str) -> Option<Vec<u8>> {
+    let mut result = vec![];
+    let mut bytes = token.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            result.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            result.push(b);
+        }
+    }
+    Some(result)
+}
+
+fn encode_option(bytes: Option<&[u8]>) -> String {
+    bytes.map_or("-".to_string(), encode_bytes)
+}
+
+/// Encode a map as its keys and values, in key order.
+fn encode_map<K: AsRef<str>, V: AsRef<str>>(map: &HashMap<K, V>) -> String {
+    let sorted: BTreeMap<&str, &str> = map.iter().map(|(k, v)| (k.as_ref(), v.as_ref())).collect();
+    sorted
+        .into_iter()
+        .map(|(k, v)| {
+            format!(
+                "{} {}",
+                encode_bytes(k.as_bytes()),
+                encode_bytes(v.as_bytes())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn encode_ids(ids: impl Iterator<Item = usize>) -> String {
+    ids.map(|id| id.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        RecordingCredential, RecordingCredentialBuilder, ReplayCredentialBuilder, decode_bytes,
+        encode_bytes,
+    };
+    use crate::credential::{CredentialBuilderApi, StoreStatus};
+    use crate::mock::{MockCredential, MockStore};
+    use crate::{Entry, Error};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// A writer whose output can be read while it's owned by a recorder.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    /// Make some calls, returning a description of their results.
+    ///
+    /// The given function is called to add a duplicate for the entry's credential.
+    fn exercise(builder: &crate::CredentialBuilder, add_duplicate: impl Fn(&Entry)) -> Vec<String> {
+        let mut results = vec![];
+        let entry = Entry::new_with_credential(builder.build(None, "my service", "user").unwrap());
+        results.push(format!("{:?}", entry.get_password()));
+        entry.set_password("hello % world").unwrap();
+        results.push(format!("{:?}", entry.get_password()));
+        entry.lock().unwrap();
+        results.push(format!("{:?}", entry.is_locked()));
+        results.push(format!("{:?}", entry.get_password()));
+        entry.unlock().unwrap();
+        results.push(format!("{:?}", entry.set_secret_if(None, b"\x80")));
+        entry.set_secret(b"\x80\x00").unwrap();
+        results.push(format!("{:?}", entry.get_password()));
+        results.push(format!(
+            "{:?}",
+            entry.get_zeroizing_secret().map(|s| s.len())
+        ));
+        let mut attributes = entry.get_attributes().unwrap();
+        attributes.remove("label");
+        results.push(format!("{attributes:?}"));
+        entry
+            .update_attributes(&HashMap::from([("color", "blue")]))
+            .unwrap();
+        let found = builder.search(&HashMap::from([("color", "blue")])).unwrap();
+        results.push(format!("{}", found.len()));
+        add_duplicate(&entry);
+        match entry.get_secret() {
+            Err(Error::Ambiguous(creds)) => {
+                results.push(format!("{}", creds.len()));
+                creds[1].delete_credential().unwrap();
+            }
+            other => panic!("Expected ambiguous result, got {other:?}"),
+        }
+        results.push(format!("{:?}", entry.get_secret()));
+        entry.delete_credential().unwrap();
+        results.push(format!("{:?}", entry.delete_credential()));
+        results.push(format!("{:?}", builder.probe().status));
+        results
+    }
+
+    #[test]
+    fn test_encoding() {
+        for bytes in [&b""[..], b"plain", b"with space", b"100%", b"\x00\xff\n"] {
+            let token = encode_bytes(bytes);
+            assert!(!token.contains(char::is_whitespace));
+            assert_eq!(decode_bytes(&token[1..]).unwrap(), bytes);
+        }
+        assert!(decode_bytes("%4").is_none());
+        assert!(decode_bytes("%zz").is_none());
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let buffer = SharedBuffer::default();
+        let store = MockStore::new();
+        let builder = RecordingCredentialBuilder::with_writer(
+            store.credential_builder(),
+            Box::new(buffer.clone()),
+        )
+        .unwrap();
+        let recorded = exercise(&builder, |entry| {
+            let recording: &RecordingCredential = entry.get_credential().downcast_ref().unwrap();
+            let mock: &MockCredential = recording.inner().as_any().downcast_ref().unwrap();
+            mock.add_duplicate(b"duplicate");
+        });
+        assert_eq!(recorded[0], "Err(NoEntry)");
+        assert_eq!(recorded[2], "Ok(true)");
+        assert_eq!(recorded[3], "Err(Locked)");
+        assert_eq!(recorded[5], "Err(BadEncoding([128, 0]))");
+        let text = buffer.text();
+        assert!(text.contains("builder build - \"my%20service \"user -> ok 0\n"));
+        // the replay has no mock to add a duplicate to, and doesn't need one
+        let replay = ReplayCredentialBuilder::from_recording(&text).unwrap();
+        assert_eq!(exercise(&replay, |_| {}), recorded);
+        replay.assert_finished();
+    }
+
+    #[test]
+    fn test_errors() {
+        let text = "builder build - \"s \"u -> ok 3\n\
+            3 get_password -> err NoStorageAccess \"locked%20keyring\n\
+            3 get_secret -> err PlatformFailure \"oops\n\
+            3 set_password \"x -> err TooLong \"password 10\n\
+            3 delete_credential -> err Invalid \"target \"cannot%20be%20empty\n\
+            builder probe -> ok Locked \"collection%20is%20locked\n";
+        let replay = ReplayCredentialBuilder::from_recording(text).unwrap();
+        let entry = Entry::new_with_credential(replay.build(None, "s", "u").unwrap());
+        match entry.get_password() {
+            Err(Error::NoStorageAccess(err)) => assert_eq!(err.to_string(), "locked keyring"),
+            other => panic!("Wrong replayed result: {other:?}"),
+        }
+        assert!(matches!(entry.get_secret(), Err(Error::PlatformFailure(_))));
+        assert!(matches!(
+            entry.set_password("x"),
+            Err(Error::TooLong(_, 10))
+        ));
+        assert!(matches!(
+            entry.delete_credential(),
+            Err(Error::Invalid(_, _))
+        ));
+        assert_eq!(replay.remaining(), 1);
+        assert_eq!(replay.probe().status, StoreStatus::Locked);
+        replay.assert_finished();
+        assert!(matches!(
+            ReplayCredentialBuilder::from_recording("builder probe"),
+            Err(Error::Invalid(_, _))
+        ));
+        assert!(matches!(
+            ReplayCredentialBuilder::from_recording("0 get_secret -> ok \"%g0"),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Replay diverged at line 2")]
+    fn test_divergence() {
+        let text = "builder build - \"s \"u -> ok 0\n0 set_password \"a -> ok\n";
+        let replay = ReplayCredentialBuilder::from_recording(text).unwrap();
+        let entry = Entry::new_with_credential(replay.build(None, "s", "u").unwrap());
+        entry.set_password("b").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "after the recording ended")]
+    fn test_extra_call() {
+        let replay = ReplayCredentialBuilder::from_recording("").unwrap();
+        let _ = replay.build(None, "s", "u");
+    }
+
+    #[test]
+    #[should_panic(expected = "Replay isn't finished")]
+    fn test_unfinished() {
+        let replay =
+            ReplayCredentialBuilder::from_recording("builder probe -> ok Available \"").unwrap();
+        replay.assert_finished();
+    }
+}
@@ -0,0 +1,252 @@
+/*!
+
+# Zeroizing secret values
+
+Passwords and secrets retrieved as plain [String] and `Vec<u8>` values
+leave copies of their content behind in freed memory.  The types in this
+module hold retrieved passwords and secrets in buffers that are
+overwritten with zeros when the value is dropped, and their [Debug]
+output never reveals their content.  You get values of these types by calling
+[get_zeroizing_password](crate::Entry::get_zeroizing_password) and
+[get_zeroizing_secret](crate::Entry::get_zeroizing_secret) on an entry.
+
+If the `mlock` feature is specified, then on Unix platforms the buffers
+holding these values are also locked into memory (with `mlock(2)`) so that
+they are never written to swap.  Locking is done on a best-effort basis:
+the number of pages a process can lock is limited, so use
+[is_locked](SecretBytes::is_locked) if you need to know whether a
+particular value was locked.
+
+Since this crate can only control copies of secrets that it makes,
+a credential store that doesn't implement the
+[zeroizing](crate::credential::CredentialApi::get_zeroizing_secret) calls
+itself will return values that were copied out of a non-zeroizing buffer.
+The mock and secret-service stores implement them; the Windows, macOS, and iOS
+stores use the default implementations, which copy the secret out of
+the buffer returned by [get_secret](crate::credential::CredentialApi::get_secret).
+ */
+use zeroize::Zeroize;
+
+/// A secret (byte array) that is zeroed when dropped.
+pub struct SecretBytes {
+    bytes: Vec<u8>,
+    locked: bool,
+}
+
+impl SecretBytes {
+    /// Take ownership of the given bytes.
+    ///
+    /// The bytes are not copied, so the caller should make sure there
+    /// are no other copies of them.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        let locked = lock_memory(bytes.as_ptr(), bytes.capacity());
+        Self { bytes, locked }
+    }
+
+    /// Return a reference to the secret.
+    pub fn expose_secret(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Whether the memory holding the secret is locked against swapping.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// The length of the secret in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether the secret is empty.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Give up ownership of the bytes and their lock without zeroing them.
+    fn into_parts(mut self) -> (Vec<u8>, bool) {
+        let locked = std::mem::replace(&mut self.locked, false);
+        (std::mem::take(&mut self.bytes), locked)
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl Clone for SecretBytes {
+    fn clone(&self) -> Self {
+        Self::new(self.bytes.clone())
+    }
+}
+
+impl std::fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SecretBytes(<{} bytes redacted>)", self.bytes.len())
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        let (ptr, len) = (self.bytes.as_ptr(), self.bytes.capacity());
+        self.bytes.zeroize();
+        if self.locked {
+            unlock_memory(ptr, len);
+        }
+    }
+}
+
+/// A password (UTF-8 string) that is zeroed when dropped.
+pub struct SecretString {
+    string: String,
+    locked: bool,
+}
+
+impl SecretString {
+    /// Take ownership of the given string.
+    ///
+    /// The string is not copied, so the caller should make sure there
+    /// are no other copies of it.
+    pub fn new(string: String) -> Self {
+        let locked = lock_memory(string.as_ptr(), string.capacity());
+        Self { string, locked }
+    }
+
+    /// Return a reference to the password.
+    pub fn expose_secret(&self) -> &str {
+        &self.string
+    }
+
+    /// Whether the memory holding the password is locked against swapping.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// The length of the password in bytes.
+    pub fn len(&self) -> usize {
+        self.string.len()
+    }
+
+    /// Whether the password is empty.
+    pub fn is_empty(&self) -> bool {
+        self.string.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(string: String) -> Self {
+        Self::new(string)
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> Self {
+        Self::new(self.string.clone())
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SecretString(<redacted>)")
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        let (ptr, len) = (self.string.as_ptr(), self.string.capacity());
+        self.string.zeroize();
+        if self.locked {
+            unlock_memory(ptr, len);
+        }
+    }
+}
+
+/// Try to interpret a secret as a password, without copying it.
+///
+/// If the secret is not UTF-8, the returned [BadEncoding](crate::Error::BadEncoding)
+/// error holds the (no longer zeroizing) secret bytes.
+pub fn decode_zeroizing_password(secret: SecretBytes) -> crate::Result<SecretString> {
+    let (bytes, locked) = secret.into_parts();
+    match String::from_utf8(bytes) {
+        Ok(string) => Ok(SecretString { string, locked }),
+        Err(err) => {
+            let bytes = err.into_bytes();
+            if locked {
+                unlock_memory(bytes.as_ptr(), bytes.capacity());
+            }
+            Err(crate::Error::BadEncoding(bytes))
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "mlock"))]
+fn lock_memory(ptr: *const u8, len: usize) -> bool {
+    if len == 0 {
+        return false;
+    }
+    // SAFETY: the pointer and length describe a live allocation owned by the caller.
+    let locked = unsafe { libc::mlock(ptr as *const libc::c_void, len) } == 0;
+    if !locked {
+        log::debug!(
+            "couldn't lock secret memory: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    locked
+}
+
+#[cfg(not(all(unix, feature = "mlock")))]
+fn lock_memory(_ptr: *const u8, _len: usize) -> bool {
+    false
+}
+
+#[cfg(all(unix, feature = "mlock"))]
+fn unlock_memory(ptr: *const u8, len: usize) {
+    // SAFETY: the pointer and length describe an allocation that was locked by us.
+    if unsafe { libc::munlock(ptr as *const libc::c_void, len) } != 0 {
+        log::debug!(
+            "couldn't unlock secret memory: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(all(unix, feature = "mlock")))]
+fn unlock_memory(_ptr: *const u8, _len: usize) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let password = SecretString::from("hunter2".to_string());
+        let secret = SecretBytes::from(b"hunter2".to_vec());
+        assert!(!format!("{password:?}").contains("hunter2"));
+        assert!(!format!("{secret:?}").contains("104")); // the byte value of 'h'
+        assert_eq!(password.expose_secret(), "hunter2");
+        assert_eq!(secret.expose_secret(), b"hunter2");
+    }
+
+    #[test]
+    fn test_decode() {
+        let password = decode_zeroizing_password(SecretBytes::from("桜".as_bytes().to_vec()))
+            .expect("Can't decode UTF-8 secret");
+        assert_eq!(password.expose_secret(), "桜");
+        match decode_zeroizing_password(SecretBytes::from(b"\x80".to_vec())) {
+            Err(crate::Error::BadEncoding(bytes)) => assert_eq!(bytes, b"\x80"),
+            other => panic!("Bad password decode gave wrong result: {other:?}"),
+        }
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "mlock"))]
+    fn test_lock() {
+        let secret = SecretBytes::from(vec![1u8; 64]);
+        assert!(secret.is_locked(), "Secret memory wasn't locked");
+        let password = decode_zeroizing_password(secret).unwrap();
+        assert!(password.is_locked(), "Lock wasn't transferred on decode");
+    }
+}
@@ -37,6 +37,15 @@ specified for the entry, in which case the item
 will be created in a collection (created if necessary)
 that is labeled with the specified target.
 
+Searches for entries (see [Entry::search](crate::Entry::search)) are
+done at the service level, like item lookups.  The `target`, `service`,
+and `user` values in the search spec are matched against the
+`target`, `service`, and `username` attributes, and all other
+values in the spec are matched against the attributes of the same name.
+(Because the `label` is not actually an attribute, it can't be searched on.)
+The returned entries wrap credentials made from the found items,
+as with [new_from_item](SsCredential::new_from_item).
+
 Setting the password on an entry will always update the password on an
 existing item in preference to creating a new item.
 This provides better compatibility with 3rd party clients, as well as earlier
@@ -44,6 +53,65 @@ versions of this crate, that may already
 have created items that match the entry, and thus reduces the chance
 of ambiguity in later searches.
 
+Each credential builder makes a single connection to the secret service
+the first time it's needed, and shares it with the credentials it builds,
+so a sequence of operations doesn't pay for a new connection (and
+session negotiation) each time.  Credentials made directly, with
+[new_with_target](SsCredential::new_with_target) and the like, each
+have their own connection.  If the connection is lost, or the secret service
+restarts, the operation that notices is retried on a new connection.
+
+All the connections are owned by a single background thread, which is
+started the first time a connection is needed.  Every operation on every
+credential is sent to that thread, which does them one at a time in the
+order they were made.  So clients can use this store from as many threads
+as they like without the D-Bus calls interfering with each other.
+
+Because operations are done one at a time, an operation that is waiting for
+the user to answer a prompt (see below) holds up every later operation,
+on every credential, until the prompt is answered, dismissed, or times out.
+That's why prompts time out after [DEFAULT_PROMPT_TIMEOUT] unless you say
+otherwise.  Clients that can't afford that wait should use a shorter timeout,
+or tell their builder never to prompt.
+
+To load many secrets quickly, use
+[get_secrets_many](crate::credential::CredentialBuilderApi::get_secrets_many)
+on the builder (or [Entry::get_secrets_many](crate::Entry::get_secrets_many)).
+The item for each credential is still looked up separately, but the secrets are
+then fetched with a single `GetSecrets` call for each builder that made the credentials,
+over that builder's connection.  The secret service library
+doesn't give access to its own session, so that call is made on a second
+connection with a plain session; if the builder's session is encrypted,
+this is not done: a warning is logged, and each secret is fetched separately
+over the encrypted session.
+
+## Session encryption
+
+Secrets can be sent over the bus in the clear ([Plain](EncryptionType::Plain))
+or encrypted with a key negotiated when the session is opened
+([Dh](EncryptionType::Dh)).  By default, sessions are encrypted if the
+`encrypted` feature was specified and plain otherwise, but you can choose at runtime
+with [SsCredentialBuilder::with_encryption] and [SsCredential::set_encryption].
+(Some minimal secret services, such as the fake used for testing,
+only support plain sessions.)
+
+## Prompting
+
+Using an item in a locked collection, or creating a new collection, makes the
+secret service prompt the user (e.g., for the password that unlocks the collection).
+By default, the prompt has no parent window, and the operation waits up to
+[DEFAULT_PROMPT_TIMEOUT] for the user to answer it.  A builder's
+[set_prompt_options](SsCredentialBuilder::set_prompt_options) changes that:
+its credentials can be told never to prompt (so that operations which need
+a prompt fail at once with a [Locked](ErrorCode::Locked) error, which is what you
+want on headless machines), to give up on prompts after a different timeout
+(or never), or to attach their prompts to a given window.
+
+Clients that want to unlock once (say, at the start of a session) and lock
+again when they're done can use [Entry::unlock](crate::Entry::unlock) and
+[Entry::lock](crate::Entry::lock).  The secret service locks whole collections,
+so these unlock and lock every item in the collection that holds the entry's item.
+
 ## Headless usage
 
 If you must use the secret-service on a headless linux box,
@@ -70,6 +138,13 @@ function unlock-keyring ()
 For an excellent treatment of all the headless dbus issues, see
 [this answer on ServerFault](https://serverfault.com/a/906224/79617).
 
+## Testing
+
+If you specify the `fake-secret-service` feature, the
+[fake_secret_service](crate::fake_secret_service) module provides
+a fake secret service, on a private D-Bus, that this store's tests
+(and yours) can use instead of a running gnome-keyring.
+
 ## Usage - not! - on Windows Subsystem for Linux
 
 As noted in
@@ -78,12 +153,27 @@ there is no "default" collection defined under WSL.  So
 this keystore doesn't work "out of the box" on WSL.  See the
 issue for more details and possible workarounds.
  */
+use std::cell::{Cell, OnceCell};
 use std::collections::HashMap;
-
-use dbus_secret_service::{Collection, EncryptionType, Error, Item, SecretService};
-
-use super::credential::{Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi};
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, mpsc};
+use std::time::{Duration, Instant};
+
+use dbus::Path;
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::Connection as DbusConnection;
+use dbus::message::MatchRule;
+pub use dbus_secret_service::EncryptionType;
+use dbus_secret_service::{Collection, Error, Item, SecretService};
+use log::debug;
+
+use super::credential::{
+    Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi, CredentialCapabilities,
+    ProbeReport, StoreStatus,
+};
 use super::error::{Error as ErrorCode, Result, decode_password};
+use super::secret::{SecretBytes, SecretString, decode_zeroizing_password};
 
 /// The representation of an item in the secret-service.
 ///
@@ -98,6 +188,7 @@ pub struct SsCredential {
     pub attributes: HashMap<String, String>,
     pub label: String,
     target: Option<String>,
+    connection: Arc<SsConnection>,
 }
 
 impl CredentialApi for SsCredential {
@@ -122,8 +213,10 @@ impl CredentialApi for SsCredential {
     /// When creating, the item is put into a collection named by the credential's `target`
     /// attribute.  
     fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let secret = Arc::new(SecretBytes::new(secret.to_vec()));
+        let existing = secret.clone();
         // first try to find a unique, existing, matching item and set its password
-        match self.map_matching_items(|i| set_item_secret(i, secret), true) {
+        match self.map_matching_items(move |i| set_item_secret(i, existing.expose_secret()), true) {
             Ok(_) => return Ok(()),
             Err(ErrorCode::NoEntry) => {}
             Err(err) => return Err(err),
@@ -132,23 +225,28 @@ impl CredentialApi for SsCredential {
         // an item, the credential must have an explicit target.  All entries created with
         // the [new] or [new_with_target] commands will have explicit targets.  But entries
         // created to wrap 3rd-party items that don't have `target` attributes may not.
-        #[cfg(feature = "encrypted")]
-        let session_type = EncryptionType::Dh;
-        #[cfg(not(feature = "encrypted"))]
-        let session_type = EncryptionType::Plain;
-        let ss = SecretService::connect(session_type).map_err(platform_failure)?;
-        let name = self.target.as_ref().ok_or_else(empty_target)?;
-        let collection = get_collection(&ss, name).or_else(|_| create_collection(&ss, name))?;
-        collection
-            .create_item(
-                self.label.as_str(),
-                self.all_attributes(),
-                secret,
-                true, // replace
-                "text/plain",
-            )
-            .map_err(platform_failure)?;
-        Ok(())
+        let name = self.target.clone().ok_or_else(empty_target)?;
+        let this = self.clone();
+        self.connection.call(move |ss, prompter| {
+            let collection = match find_collection(ss, &name) {
+                Ok(collection) => {
+                    prompter.unlock(vec![collection.path.clone()])?;
+                    collection
+                }
+                Err(ErrorCode::NoEntry) => prompter.create_collection(ss, &name)?,
+                Err(err) => return Err(err),
+            };
+            collection
+                .create_item(
+                    this.label.as_str(),
+                    this.all_attributes(),
+                    secret.expose_secret(),
+                    true, // replace
+                    "text/plain",
+                )
+                .map_err(platform_failure)?;
+            Ok(())
+        })
     }
 
     /// Gets the password on a unique matching item, if it exists.
@@ -160,7 +258,7 @@ impl CredentialApi for SsCredential {
     /// error with a credential for each matching item.
     fn get_password(&self) -> Result<String> {
         let passwords: Vec<String> = self.map_matching_items(get_item_password, true)?;
-        Ok(passwords[0].clone())
+        Ok(passwords.into_iter().next().unwrap())
     }
 
     /// Gets the secret on a unique matching item, if it exists.
@@ -172,7 +270,23 @@ impl CredentialApi for SsCredential {
     /// error with a credential for each matching item.
     fn get_secret(&self) -> Result<Vec<u8>> {
         let secrets: Vec<Vec<u8>> = self.map_matching_items(get_item_secret, true)?;
-        Ok(secrets[0].clone())
+        Ok(secrets.into_iter().next().unwrap())
+    }
+
+    /// Gets the password on a unique matching item, if it exists, in a zeroizing buffer.
+    ///
+    /// Errors are the same as for [get_password](SsCredential::get_password).
+    fn get_zeroizing_password(&self) -> Result<SecretString> {
+        decode_zeroizing_password(self.get_zeroizing_secret()?)
+    }
+
+    /// Gets the secret on a unique matching item, if it exists, in a zeroizing buffer.
+    ///
+    /// Errors are the same as for [get_secret](SsCredential::get_secret).
+    fn get_zeroizing_secret(&self) -> Result<SecretBytes> {
+        let secrets: Vec<SecretBytes> =
+            self.map_matching_items(|i| get_item_secret(i).map(SecretBytes::new), true)?;
+        Ok(secrets.into_iter().next().unwrap())
     }
 
     /// Get attributes on a unique matching item, if it exists
@@ -184,7 +298,17 @@ impl CredentialApi for SsCredential {
 
     /// Update attributes on a unique matching item, if it exists
     fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
-        self.map_matching_items(|i| update_item_attributes(i, attributes), true)?;
+        let attributes: HashMap<String, String> = attributes
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        self.map_matching_items(
+            move |i| {
+                let attributes = attributes.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+                update_item_attributes(i, &attributes.collect())
+            },
+            true,
+        )?;
         Ok(())
     }
 
@@ -200,6 +324,58 @@ impl CredentialApi for SsCredential {
         Ok(())
     }
 
+    /// Whether any item matching this credential is locked.
+    ///
+    /// If there are no matching items, this checks the collection named by
+    /// the credential's `target` attribute.  If that doesn't exist either,
+    /// returns a [NoEntry](ErrorCode::NoEntry) error.
+    fn is_locked(&self) -> Result<bool> {
+        let this = self.clone();
+        self.connection.call(move |ss, _| {
+            let objects = this.lockable_objects_on(ss)?;
+            Ok(objects.iter().any(|(_, locked)| *locked))
+        })
+    }
+
+    /// Unlock the items matching this credential, prompting the user if necessary.
+    ///
+    /// Since the secret service locks whole collections, this unlocks every
+    /// item in the collections that hold the matching items.  If there are no
+    /// matching items, the collection named by the credential's `target`
+    /// attribute is unlocked.  If that doesn't exist either,
+    /// returns a [NoEntry](ErrorCode::NoEntry) error.
+    fn unlock(&self) -> Result<()> {
+        let this = self.clone();
+        self.connection.call(move |ss, prompter| {
+            let objects = this.lockable_objects_on(ss)?;
+            let locked = objects.into_iter().filter(|(_, locked)| *locked);
+            prompter.unlock(locked.map(|(path, _)| path).collect())
+        })
+    }
+
+    /// Lock the items matching this credential.
+    ///
+    /// Since the secret service locks whole collections, this locks every
+    /// item in the collections that hold the matching items.  If there are no
+    /// matching items, the collection named by the credential's `target`
+    /// attribute is locked.  If that doesn't exist either,
+    /// returns a [NoEntry](ErrorCode::NoEntry) error.
+    fn lock(&self) -> Result<()> {
+        let this = self.clone();
+        self.connection.call(move |ss, prompter| {
+            let objects = this.lockable_objects_on(ss)?;
+            prompter.lock(objects.into_iter().map(|(path, _)| path).collect())
+        })
+    }
+
+    /// A builder that shares this credential's connection, so its secret
+    /// can be fetched together with others (see the module docs).
+    fn batch_builder(&self) -> Option<Box<CredentialBuilder>> {
+        Some(Box::new(SsCredentialBuilder {
+            connection: self.connection.clone(),
+        }))
+    }
+
     /// Return the underlying credential object with an `Any` type so that it can
     /// be downgraded to an [SsCredential] for platform-specific processing.
     fn as_any(&self) -> &dyn std::any::Any {
@@ -240,6 +416,7 @@ impl SsCredential {
                 env!("CARGO_PKG_VERSION"),
             ),
             target: Some(target.to_string()),
+            connection: Arc::default(),
         })
     }
 
@@ -260,6 +437,7 @@ impl SsCredential {
                 env!("CARGO_PKG_VERSION"),
             ),
             target: None,
+            connection: Arc::default(),
         })
     }
 
@@ -268,19 +446,46 @@ impl SsCredential {
     /// The created credential will have all the attributes and label
     /// of the underlying item, so you can examine them.
     pub fn new_from_item(item: &Item) -> Result<Self> {
+        Self::new_from_item_on(item, &Arc::default())
+    }
+
+    /// Create a credential from an underlying item, which shares the given connection.
+    fn new_from_item_on(item: &Item, connection: &Arc<SsConnection>) -> Result<Self> {
         let attributes = item.get_attributes().map_err(decode_error)?;
         let target = attributes.get("target").cloned();
         Ok(Self {
             attributes,
             label: item.get_label().map_err(decode_error)?,
             target,
+            connection: connection.clone(),
         })
     }
 
+    /// The encryption used for this credential's session with the secret service.
+    ///
+    /// See [set_encryption](SsCredential::set_encryption).
+    pub fn encryption(&self) -> EncryptionType {
+        self.connection.encryption()
+    }
+
+    /// Choose the encryption used for this credential's session with the secret service.
+    ///
+    /// The default is [Dh](EncryptionType::Dh) if the `encrypted` feature
+    /// was specified, and [Plain](EncryptionType::Plain) otherwise.  Changing
+    /// it gives this credential a connection of its own; credentials made by a builder
+    /// share the builder's connection, and so its encryption,
+    /// unless they are changed this way.
+    pub fn set_encryption(&mut self, encryption: EncryptionType) {
+        let prompts = self.connection.prompts.clone();
+        self.connection = Arc::new(SsConnection::new(encryption, prompts));
+    }
+
     /// Construct a credential for this credential's underlying matching item,
     /// if there is exactly one.
     pub fn new_from_matching_item(&self) -> Result<Self> {
-        let credentials = self.map_matching_items(Self::new_from_item, true)?;
+        let connection = self.connection.clone();
+        let credentials =
+            self.map_matching_items(move |i| Self::new_from_item_on(i, &connection), true)?;
         Ok(credentials[0].clone())
     }
 
@@ -304,6 +509,12 @@ impl SsCredential {
     /// Map a function over the items matching this credential.
     ///
     /// Items are unlocked before the function is applied.
+    /// The function is called on the thread that owns the connections
+    /// to the secret service (see the module docs), which is why it must be
+    /// `Send + 'static`.  It may use other secret-service credentials
+    /// (including clones of this one), but their calls are then made on a
+    /// temporary connection of their own, since the thread that owns
+    /// the usual one is busy calling the function.
     ///
     /// If `require_unique` is true, and there are no matching items, then
     /// a [NoEntry](ErrorCode::NoEntry) error is returned.
@@ -312,20 +523,32 @@ impl SsCredential {
     /// with a vector containing one
     /// credential for each of the matching items.
     pub fn map_matching_items<F, T>(&self, f: F, require_unique: bool) -> Result<Vec<T>>
+    where
+        F: Fn(&Item) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let this = self.clone();
+        self.connection
+            .call(move |ss, prompter| this.map_matching_items_on(ss, prompter, &f, require_unique))
+    }
+
+    /// Map a function over the items matching this credential, using the given connection.
+    fn map_matching_items_on<F, T>(
+        &self,
+        ss: &SecretService,
+        prompter: &Prompter,
+        f: F,
+        require_unique: bool,
+    ) -> Result<Vec<T>>
     where
         F: Fn(&Item) -> Result<T>,
         T: Sized,
     {
-        #[cfg(feature = "encrypted")]
-        let session_type = EncryptionType::Dh;
-        #[cfg(not(feature = "encrypted"))]
-        let session_type = EncryptionType::Plain;
-        let ss = SecretService::connect(session_type).map_err(platform_failure)?;
         let attributes: HashMap<&str, &str> = self.search_attributes(false).into_iter().collect();
         let search = ss.search_items(attributes).map_err(decode_error)?;
         let count = search.locked.len() + search.unlocked.len();
         if count == 0 && matches!(self.target.as_ref(), Some(t) if t == "default") {
-            return self.map_matching_legacy_items(&ss, f, require_unique);
+            return self.map_matching_legacy_items(ss, f, require_unique);
         }
         if require_unique {
             if count == 0 {
@@ -333,23 +556,26 @@ impl SsCredential {
             } else if count > 1 {
                 let mut creds: Vec<Box<Credential>> = vec![];
                 for item in search.locked.iter().chain(search.unlocked.iter()) {
-                    let cred = Self::new_from_item(item)?;
+                    let cred = Self::new_from_item_on(item, &self.connection)?;
                     creds.push(Box::new(cred))
                 }
                 return Err(ErrorCode::Ambiguous(creds));
             }
         }
+        prompter.unlock(search.locked.iter().map(|i| i.path.clone()).collect())?;
         let mut results: Vec<T> = vec![];
-        for item in search.unlocked.iter() {
-            results.push(f(item)?);
-        }
-        for item in search.locked.iter() {
-            item.unlock().map_err(decode_error)?;
+        for item in search.unlocked.iter().chain(search.locked.iter()) {
             results.push(f(item)?);
         }
         Ok(results)
     }
 
+    /// Get the secret of the one item matching this credential, using the given connection.
+    fn get_secret_on(&self, ss: &SecretService, prompter: &Prompter) -> Result<Vec<u8>> {
+        let secrets = self.map_matching_items_on(ss, prompter, get_item_secret, true)?;
+        Ok(secrets.into_iter().next().unwrap())
+    }
+
     /// Map a function over items that older versions of keyring
     /// would have matched against this credential.
     ///
@@ -392,7 +618,7 @@ impl SsCredential {
             } else if search.len() > 1 {
                 let mut creds: Vec<Box<Credential>> = vec![];
                 for item in search.iter() {
-                    let cred = Self::new_from_item(item)?;
+                    let cred = Self::new_from_item_on(item, &self.connection)?;
                     creds.push(Box::new(cred))
                 }
                 return Err(ErrorCode::Ambiguous(creds));
@@ -405,6 +631,27 @@ impl SsCredential {
         Ok(results)
     }
 
+    /// The items that match this credential, or (if there are none) the
+    /// collection named by its target, each with whether it's locked.
+    fn lockable_objects_on(&self, ss: &SecretService) -> Result<Vec<(Path<'static>, bool)>> {
+        let search = ss
+            .search_items(self.search_attributes(false))
+            .map_err(decode_error)?;
+        let locked = search.locked.iter().map(|item| (item.path.clone(), true));
+        let unlocked = search
+            .unlocked
+            .iter()
+            .map(|item| (item.path.clone(), false));
+        let mut objects: Vec<(Path<'static>, bool)> = locked.chain(unlocked).collect();
+        if objects.is_empty() {
+            let name = self.target.as_deref().ok_or(ErrorCode::NoEntry)?;
+            let collection = find_collection(ss, name)?;
+            let locked = collection.is_locked().map_err(decode_error)?;
+            objects.push((collection.path.clone(), locked));
+        }
+        Ok(objects)
+    }
+
     /// Using strings in the credential map makes managing the lifetime
     /// of the credential much easier.  But since the secret service expects
     /// a map from &str to &str, we have this utility to transform the
@@ -429,24 +676,124 @@ impl SsCredential {
     }
 }
 
+/// How the secret service may prompt the user, e.g., to unlock a collection.
+///
+/// The default is to prompt whenever it's needed, with no parent window,
+/// and to wait up to [DEFAULT_PROMPT_TIMEOUT] for the user to answer.
+/// Since options may be added in later releases, start from the default
+/// and change it with the `with_` methods:
+/// ```
+/// # use keyring::secret_service::PromptOptions;
+/// # use std::time::Duration;
+/// let options = PromptOptions::default().with_timeout(Duration::from_secs(30));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PromptOptions {
+    /// Never prompt: operations that would need a prompt fail with a
+    /// [Locked](ErrorCode::Locked) error instead.
+    pub never_prompt: bool,
+    /// How long to wait for the user to answer a prompt before dismissing
+    /// it, in which case the operation fails with a
+    /// [NoStorageAccess](ErrorCode::NoStorageAccess) error.
+    /// `None` means wait as long as it takes, which holds up all the other
+    /// secret-service operations in the process for that long (see the
+    /// [module docs](self)).
+    pub timeout: Option<Duration>,
+    /// The id of the window the prompt belongs to, in the form described by the
+    /// [Secret Service API](https://specifications.freedesktop.org/secret-service-spec/latest/prompts.html)
+    /// (e.g., `x11:` followed by the hex X11 window id).
+    /// An empty id means no parent window.
+    pub window_id: String,
+}
+
+/// How long prompts are waited for unless the [PromptOptions] say otherwise.
+pub const DEFAULT_PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+impl Default for PromptOptions {
+    fn default() -> Self {
+        Self {
+            never_prompt: false,
+            timeout: Some(DEFAULT_PROMPT_TIMEOUT),
+            window_id: String::new(),
+        }
+    }
+}
+
+impl PromptOptions {
+    /// Set whether to never prompt (see [never_prompt](PromptOptions::never_prompt)).
+    pub fn with_never_prompt(mut self, never_prompt: bool) -> Self {
+        self.never_prompt = never_prompt;
+        self
+    }
+
+    /// Set how long to wait for prompts (see [timeout](PromptOptions::timeout)).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the window prompts belong to (see [window_id](PromptOptions::window_id)).
+    pub fn with_window_id(mut self, window_id: &str) -> Self {
+        self.window_id = window_id.to_string();
+        self
+    }
+}
+
 /// The builder for secret-service credentials
+///
+/// The builder and the credentials it builds share one connection
+/// to the secret service, which is made when it's first needed.
 #[derive(Debug, Default)]
-pub struct SsCredentialBuilder {}
+pub struct SsCredentialBuilder {
+    connection: Arc<SsConnection>,
+}
+
+impl SsCredentialBuilder {
+    /// Create a builder whose connection uses the given session encryption.
+    ///
+    /// The [default](SsCredentialBuilder::default) builder uses
+    /// [Dh](EncryptionType::Dh) if the `encrypted` feature was specified,
+    /// and [Plain](EncryptionType::Plain) otherwise.
+    pub fn with_encryption(encryption: EncryptionType) -> Self {
+        Self {
+            connection: Arc::new(SsConnection::new(encryption, PromptOptions::default())),
+        }
+    }
+
+    /// The session encryption used by this builder and its credentials.
+    pub fn encryption(&self) -> EncryptionType {
+        self.connection.encryption()
+    }
+
+    /// Choose how the credentials built from now on may prompt the user.
+    ///
+    /// This gives the builder a new connection, so credentials it
+    /// has already built keep the prompt options they were built with.
+    pub fn set_prompt_options(&mut self, options: PromptOptions) {
+        self.connection = Arc::new(SsConnection::new(self.encryption(), options));
+    }
+
+    /// How this builder's credentials may prompt the user.
+    pub fn prompt_options(&self) -> &PromptOptions {
+        &self.connection.prompts
+    }
+}
 
 /// Returns an instance of the secret-service credential builder.
 ///
 /// If secret-service is the default credential store,
 /// this is called once when an entry is first created.
 pub fn default_credential_builder() -> Box<CredentialBuilder> {
-    Box::new(SsCredentialBuilder {})
+    Box::new(SsCredentialBuilder::default())
 }
 
 impl CredentialBuilderApi for SsCredentialBuilder {
     /// Build an [SsCredential] for the given target, service, and user.
     fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
-        Ok(Box::new(SsCredential::new_with_target(
-            target, service, user,
-        )?))
+        let mut credential = SsCredential::new_with_target(target, service, user)?;
+        credential.connection = self.connection.clone();
+        Ok(Box::new(credential))
     }
 
     /// Return the underlying builder object with an `Any` type so that it can
@@ -454,17 +801,631 @@ impl CredentialBuilderApi for SsCredentialBuilder {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    /// Items have attributes and binary secrets, and can be searched for.
+    ///
+    /// Conditional sets are emulated, so they are not atomic.
+    fn capabilities(&self) -> CredentialCapabilities {
+        CredentialCapabilities {
+            attributes: true,
+            binary_secrets: true,
+            empty_service: true,
+            empty_user: true,
+            search: true,
+            locking: true,
+            ..Default::default()
+        }
+    }
+
+    /// Connect to the secret service and check its default collection.
+    ///
+    /// The store is reported as unreachable if there's no connection,
+    /// as having no default storage if there's no default collection,
+    /// and as locked if the default collection is locked.
+    fn probe(&self) -> ProbeReport {
+        let locked = self
+            .connection
+            .call(|ss, _| match ss.get_default_collection() {
+                Ok(collection) => Ok(Some(collection.is_locked().map_err(platform_failure)?)),
+                Err(Error::NoResult) => Ok(None),
+                Err(err) => Err(platform_failure(err)),
+            });
+        match locked {
+            Ok(Some(true)) => {
+                ProbeReport::new(StoreStatus::Locked, "the default collection is locked")
+            }
+            Ok(Some(false)) => {
+                ProbeReport::new(StoreStatus::Available, "the default collection is unlocked")
+            }
+            Ok(None) => ProbeReport::new(
+                StoreStatus::NoDefaultStorage,
+                "the secret service has no default collection",
+            ),
+            Err(err) => ProbeReport::new(StoreStatus::Unreachable, &err.to_string()),
+        }
+    }
+
+    /// Get the secrets of several credentials in one request to the secret service.
+    ///
+    /// The credentials needn't have been made by this builder.  The secret-service
+    /// credentials are grouped by the builder that made them, and each group is
+    /// fetched over that builder's connection (so with its encryption and prompt
+    /// options), with a single `GetSecrets` call unless its session is encrypted
+    /// (see the module docs).  Credentials from other stores are fetched one at a time.
+    fn get_secrets_many(&self, credentials: &[&Credential]) -> Vec<Result<Vec<u8>>> {
+        get_secrets_many(credentials)
+    }
+
+    /// Find all the items (in all collections) that match the given spec.
+    ///
+    /// See the module header for how the spec is interpreted.
+    fn search(&self, spec: &HashMap<&str, &str>) -> Result<Vec<Box<Credential>>> {
+        let attributes: HashMap<String, String> = spec
+            .iter()
+            .map(|(k, v)| {
+                if k.eq(&"user") {
+                    ("username".to_string(), v.to_string())
+                } else {
+                    (k.to_string(), v.to_string())
+                }
+            })
+            .collect();
+        let connection = self.connection.clone();
+        self.connection.call(move |ss, _| {
+            let attributes = attributes.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+            let search = ss
+                .search_items(attributes.collect())
+                .map_err(decode_error)?;
+            let mut creds: Vec<Box<Credential>> = vec![];
+            for item in search.unlocked.iter().chain(search.locked.iter()) {
+                let cred = SsCredential::new_from_item_on(item, &connection)?;
+                creds.push(Box::new(cred))
+            }
+            Ok(creds)
+        })
+    }
 }
 
 //
 // Secret Service utilities
 //
 
+/// A connection to the secret service that's made when it's first needed,
+/// and then shared by a builder and the credentials it builds.
+///
+/// The connection itself is owned by the worker thread, and all the calls
+/// that use it are made there.  If a call fails because the connection was lost,
+/// or because the service has forgotten our session (as happens when it restarts),
+/// the call is retried once on a new connection.
+#[derive(Debug)]
+struct SsConnection {
+    id: usize,
+    encryption: EncryptionType,
+    prompts: PromptOptions,
+}
+
+/// The worker thread's state for one [SsConnection].
+///
+/// Besides the secret service connection, this may have a raw D-Bus
+/// connection, with its own session, for fetching secrets in a batch.
+struct ConnectionState {
+    encryption: EncryptionType,
+    service: Option<SecretService>,
+    prompter: Prompter,
+    batch: Option<(DbusConnection, Path<'static>)>,
+    connects: usize,
+}
+
+/// A request for the worker thread, which is given the state of all the connections.
+type Job = Box<dyn FnOnce(&mut HashMap<usize, ConnectionState>) + Send>;
+
+impl Default for SsConnection {
+    /// A connection with the default encryption (see [default_encryption])
+    /// and prompt options.
+    fn default() -> Self {
+        Self::new(default_encryption(), PromptOptions::default())
+    }
+}
+
+impl Drop for SsConnection {
+    /// Have the worker thread close the connection (without waiting for it to).
+    fn drop(&mut self) {
+        let id = self.id;
+        let _ = worker().send(Box::new(move |connections| {
+            connections.remove(&id);
+        }));
+    }
+}
+
+impl SsConnection {
+    fn new(encryption: EncryptionType, prompts: PromptOptions) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            encryption,
+            prompts,
+        }
+    }
+
+    fn encryption(&self) -> EncryptionType {
+        copy_encryption(&self.encryption)
+    }
+
+    /// Call the given function with the connection (and the prompter
+    /// for anything that needs unlocking), on the worker thread,
+    /// connecting first if necessary.
+    fn call<T: Send + 'static>(
+        &self,
+        f: impl Fn(&SecretService, &Prompter) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        self.call_with_state(move |state| {
+            let (ss, prompter) = state.connect()?;
+            f(ss, prompter)
+        })
+    }
+
+    /// Call the given function with the worker's state for this connection.
+    fn call_with_state<T: Send + 'static>(
+        &self,
+        f: impl Fn(&mut ConnectionState) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let id = self.id;
+        let encryption = self.encryption();
+        let prompts = self.prompts.clone();
+        run(move |connections| {
+            let state = connections.entry(id).or_insert_with(|| {
+                ConnectionState::new(copy_encryption(&encryption), prompts.clone())
+            });
+            match f(state) {
+                Err(err) if is_stale(&err) => {
+                    debug!("reconnecting to the secret service after: {err}");
+                    state.service = None;
+                    state.prompter.connection.take();
+                    state.batch = None;
+                    f(state)
+                }
+                result => result,
+            }
+        })
+    }
+
+    /// The number of times this connection has been made.
+    #[cfg(test)]
+    fn connects(&self) -> usize {
+        let id = self.id;
+        run(move |connections| connections.get(&id).map_or(0, |state| state.connects))
+    }
+}
+
+impl ConnectionState {
+    fn new(encryption: EncryptionType, prompts: PromptOptions) -> Self {
+        Self {
+            encryption,
+            service: None,
+            prompter: Prompter::new(prompts),
+            batch: None,
+            connects: 0,
+        }
+    }
+
+    fn connect(&mut self) -> Result<(&SecretService, &Prompter)> {
+        if self.service.is_none() {
+            let encryption = copy_encryption(&self.encryption);
+            self.service = Some(connect(encryption, &self.prompter.options)?);
+            self.connects += 1;
+        }
+        let ss = self.service.as_ref().expect("connection was just made");
+        Ok((ss, &self.prompter))
+    }
+
+    /// Get the secrets of the given credentials.
+    ///
+    /// The results are in the same order as the credentials.  An error
+    /// is returned only if a call failed because the connection was lost
+    /// (or the session forgotten), so that the whole batch can be retried.
+    fn get_secrets(&mut self, credentials: &[SsCredential]) -> Result<Vec<Result<Vec<u8>>>> {
+        if self.encryption != EncryptionType::Plain {
+            log::warn!(
+                "can't get {} secrets in one call over an encrypted session; getting them one at a time",
+                credentials.len()
+            );
+            let (ss, prompter) = self.connect()?;
+            let results = credentials
+                .iter()
+                .map(|c| c.get_secret_on(ss, prompter))
+                .collect();
+            return unless_stale(results);
+        }
+        let (ss, prompter) = self.connect()?;
+        let paths: Vec<Result<Path<'static>>> = credentials
+            .iter()
+            .map(|c| {
+                let paths =
+                    c.map_matching_items_on(ss, prompter, |item| Ok(item.path.clone()), true)?;
+                Ok(paths.into_iter().next().unwrap())
+            })
+            .collect();
+        let items: Vec<Path<'static>> = paths
+            .iter()
+            .filter_map(|p| p.as_ref().ok())
+            .cloned()
+            .collect();
+        let secrets = self.batch_secrets(&items).unwrap_or_else(|err| {
+            debug!("can't get secrets in one call: {err}");
+            self.batch = None;
+            HashMap::new()
+        });
+        let (ss, prompter) = self.connect()?;
+        let results = credentials
+            .iter()
+            .zip(paths)
+            .map(|(credential, path)| match secrets.get(&path?) {
+                Some(secret) => Ok(secret.clone()),
+                // the item was locked again, or the batch call failed
+                None => credential.get_secret_on(ss, prompter),
+            })
+            .collect();
+        unless_stale(results)
+    }
+
+    /// Get the secrets of the given items with a single `GetSecrets` call.
+    ///
+    /// The secret service library doesn't give access to its connection or
+    /// its session, so this call is made on a D-Bus connection of our own, with a
+    /// plain session that is opened the first time it's needed.  Because that
+    /// session isn't encrypted, this must only be used when the connection's
+    /// session isn't either.
+    fn batch_secrets(
+        &mut self,
+        items: &[Path<'static>],
+    ) -> std::result::Result<HashMap<Path<'static>, Vec<u8>>, dbus::Error> {
+        if items.is_empty() {
+            return Ok(HashMap::new());
+        }
+        if self.batch.is_none() {
+            let connection = DbusConnection::new_session()?;
+            let (_, session): (Variant<Box<dyn RefArg>>, Path<'static>) = service_proxy(
+                &connection,
+            )
+            .method_call(SERVICE_INTERFACE, "OpenSession", ("plain", Variant("")))?;
+            self.batch = Some((connection, session));
+        }
+        let (connection, session) = self.batch.as_ref().expect("session was just opened");
+        type Secret = (Path<'static>, Vec<u8>, Vec<u8>, String);
+        let (secrets,): (HashMap<Path<'static>, Secret>,) = service_proxy(connection).method_call(
+            SERVICE_INTERFACE,
+            "GetSecrets",
+            (items.to_vec(), session),
+        )?;
+        Ok(secrets
+            .into_iter()
+            .map(|(path, (_, _, value, _))| (path, value))
+            .collect())
+    }
+}
+
+/// The results of fetching several secrets, unless one of them failed because
+/// the connection was stale, in which case that error (so the fetch can be retried).
+fn unless_stale(mut results: Vec<Result<Vec<u8>>>) -> Result<Vec<Result<Vec<u8>>>> {
+    match results
+        .iter()
+        .position(|r| matches!(r, Err(e) if is_stale(e)))
+    {
+        Some(i) => Err(results.swap_remove(i).unwrap_err()),
+        None => Ok(results),
+    }
+}
+
+/// Shows the prompts for unlocking items and collections, and for creating
+/// collections, as the prompt options say.
+///
+/// The secret service library always prompts with no parent window,
+/// and measures prompt timeouts in whole seconds, so the `Unlock` and
+/// `CreateCollection` calls, and their prompts, are made on a D-Bus connection
+/// of our own, which is made the first time it's needed.
+struct Prompter {
+    options: PromptOptions,
+    connection: OnceCell<DbusConnection>,
+}
+
+impl Prompter {
+    fn new(options: PromptOptions) -> Self {
+        Self {
+            options,
+            connection: OnceCell::new(),
+        }
+    }
+
+    fn connection(&self) -> Result<&DbusConnection> {
+        if let Some(connection) = self.connection.get() {
+            return Ok(connection);
+        }
+        let connection = DbusConnection::new_session().map_err(dbus_failure)?;
+        Ok(self.connection.get_or_init(|| connection))
+    }
+
+    /// Unlock the given items or collections, prompting the user if necessary.
+    fn unlock(&self, objects: Vec<Path<'static>>) -> Result<()> {
+        self.lock_or_unlock("Unlock", objects)
+    }
+
+    /// Lock the given items or collections, prompting the user if necessary.
+    fn lock(&self, objects: Vec<Path<'static>>) -> Result<()> {
+        self.lock_or_unlock("Lock", objects)
+    }
+
+    /// Call the service's `Lock` or `Unlock` method, and show its prompt if it has one.
+    fn lock_or_unlock(&self, method: &str, objects: Vec<Path<'static>>) -> Result<()> {
+        if objects.is_empty() {
+            return Ok(());
+        }
+        let connection = self.connection()?;
+        let (_, prompt): (Vec<Path<'static>>, Path<'static>) = service_proxy(connection)
+            .method_call(SERVICE_INTERFACE, method, (objects,))
+            .map_err(dbus_failure)?;
+        if &*prompt != "/" {
+            self.prompt(connection, prompt)?;
+        }
+        Ok(())
+    }
+
+    /// Create a collection with the given label, prompting the user if necessary.
+    ///
+    /// The name `default` is specially interpreted to mean the default collection.
+    fn create_collection<'a>(&self, ss: &'a SecretService, label: &str) -> Result<Collection<'a>> {
+        if label.eq("default") {
+            return ss.get_default_collection().map_err(decode_error);
+        }
+        let connection = self.connection()?;
+        let properties: PropMap = HashMap::from([(
+            "org.freedesktop.Secret.Collection.Label".to_string(),
+            Variant(Box::new(label.to_string()) as Box<dyn RefArg>),
+        )]);
+        let (path, prompt): (Path<'static>, Path<'static>) = service_proxy(connection)
+            .method_call(SERVICE_INTERFACE, "CreateCollection", (properties, ""))
+            .map_err(dbus_failure)?;
+        if &*path != "/" {
+            return Ok(Collection::new(ss, path));
+        }
+        let created = self.prompt(connection, prompt)?;
+        match created.and_then(|path| Path::new(path).ok()) {
+            Some(path) => Ok(Collection::new(ss, path)),
+            None => Err(platform_failure(Error::Parse)),
+        }
+    }
+
+    /// Show a prompt, and wait for the user to answer it.
+    ///
+    /// Returns the object path in the prompt's result, if it has one.
+    fn prompt(&self, connection: &DbusConnection, prompt: Path<'static>) -> Result<Option<String>> {
+        let proxy = connection.with_proxy(SERVICE_NAME, prompt.clone(), DBUS_TIMEOUT);
+        let dismiss = || {
+            let _: std::result::Result<(), _> = proxy.method_call(PROMPT_INTERFACE, "Dismiss", ());
+        };
+        if self.options.never_prompt {
+            dismiss();
+            return Err(ErrorCode::Locked);
+        }
+        let (sender, receiver) = mpsc::channel();
+        let rule = MatchRule::new_signal(PROMPT_INTERFACE, "Completed").with_path(prompt);
+        let token = connection
+            .add_match(
+                rule,
+                move |completed: (bool, Variant<Box<dyn RefArg>>), _, _| {
+                    let (dismissed, result) = completed;
+                    let _ = sender.send((dismissed, result.0.as_str().map(str::to_string)));
+                    false
+                },
+            )
+            .map_err(dbus_failure)?;
+        let shown = proxy.method_call(
+            PROMPT_INTERFACE,
+            "Prompt",
+            (self.options.window_id.as_str(),),
+        );
+        let answer = shown.map_err(dbus_failure).and_then(|()| {
+            let deadline = self.options.timeout.map(|timeout| Instant::now() + timeout);
+            loop {
+                if let Ok(answer) = receiver.try_recv() {
+                    return Ok(Some(answer));
+                }
+                let wait = match deadline {
+                    Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                    None => PROMPT_POLL_INTERVAL,
+                };
+                if wait.is_zero() {
+                    return Ok(None);
+                }
+                connection
+                    .process(wait.min(PROMPT_POLL_INTERVAL))
+                    .map_err(dbus_failure)?;
+            }
+        });
+        let _ = connection.remove_match(token);
+        match answer? {
+            Some((false, result)) => Ok(result),
+            Some((true, _)) => Err(no_access(Error::Prompt)),
+            None => {
+                dismiss();
+                Err(ErrorCode::NoStorageAccess("the prompt timed out".into()))
+            }
+        }
+    }
+}
+
+/// Get the secrets of several credentials, fetching the secret-service ones together.
+///
+/// This is the [get_secrets_many](CredentialBuilderApi::get_secrets_many) of every
+/// secret-service builder.
+///
+/// The secret-service credentials are grouped by the connection they share
+/// with the builder that made them, and each group is fetched over its own
+/// connection, so that its encryption and prompt options are respected.  The item
+/// for each credential is looked up in turn, but a group's secrets are all fetched
+/// with a single `GetSecrets` call (unless the group's session is encrypted;
+/// see the module docs).  Credentials from other stores are fetched one at a time.
+fn get_secrets_many(credentials: &[&Credential]) -> Vec<Result<Vec<u8>>> {
+    let mut groups: Vec<(Arc<SsConnection>, Vec<usize>, Vec<SsCredential>)> = vec![];
+    for (i, credential) in credentials.iter().enumerate() {
+        let Some(credential) = credential.as_any().downcast_ref::<SsCredential>() else {
+            continue;
+        };
+        match groups
+            .iter_mut()
+            .find(|(connection, _, _)| Arc::ptr_eq(connection, &credential.connection))
+        {
+            Some((_, indices, batch)) => {
+                indices.push(i);
+                batch.push(credential.clone());
+            }
+            None => groups.push((
+                credential.connection.clone(),
+                vec![i],
+                vec![credential.clone()],
+            )),
+        }
+    }
+    let mut results: Vec<Option<Result<Vec<u8>>>> = credentials.iter().map(|_| None).collect();
+    for (connection, indices, batch) in groups {
+        match connection.call_with_state(move |state| state.get_secrets(&batch)) {
+            Ok(secrets) => {
+                for (i, secret) in indices.into_iter().zip(secrets) {
+                    results[i] = Some(secret);
+                }
+            }
+            Err(err) => debug!("can't get secrets in a batch: {err}"),
+        }
+    }
+    results
+        .into_iter()
+        .zip(credentials)
+        .map(|(result, credential)| result.unwrap_or_else(|| credential.get_secret()))
+        .collect()
+}
+
+/// Run a job on the worker thread, and wait for its result.
+///
+/// If the job panics, the panic is passed on to the caller.
+///
+/// A job that is run from the worker thread itself (because a function passed to
+/// [map_matching_items](SsCredential::map_matching_items) used another credential)
+/// can't wait for the worker, so it's run at once, with connections of its own
+/// that are closed when it's done.
+fn run<T: Send + 'static>(
+    job: impl FnOnce(&mut HashMap<usize, ConnectionState>) -> T + Send + 'static,
+) -> T {
+    if ON_WORKER.get() {
+        debug!("secret-service call made from the worker thread; using a temporary connection");
+        return job(&mut HashMap::new());
+    }
+    let (sender, receiver) = mpsc::channel();
+    let job: Job = Box::new(move |connections| {
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| job(connections)));
+        let _ = sender.send(result);
+    });
+    worker()
+        .send(job)
+        .expect("the secret-service worker thread has exited");
+    match receiver
+        .recv()
+        .expect("the secret-service worker thread has exited")
+    {
+        Ok(result) => result,
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+thread_local! {
+    /// Whether this thread is the worker thread.
+    static ON_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The channel to the worker thread, which is started the first time it's needed.
+///
+/// The worker owns all the connections to the secret service, and handles
+/// the requests from all the threads in the order they are made.
+fn worker() -> &'static mpsc::Sender<Job> {
+    static WORKER: OnceLock<mpsc::Sender<Job>> = OnceLock::new();
+    WORKER.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        std::thread::Builder::new()
+            .name("keyring-secret-service".to_string())
+            .spawn(move || {
+                ON_WORKER.set(true);
+                let mut connections = HashMap::new();
+                for job in receiver {
+                    job(&mut connections);
+                }
+            })
+            .expect("Can't start the secret-service worker thread");
+        sender
+    })
+}
+
+/// The session encryption used unless another is chosen:
+/// [Dh](EncryptionType::Dh) if the `encrypted` feature was specified,
+/// and [Plain](EncryptionType::Plain) otherwise.
+pub fn default_encryption() -> EncryptionType {
+    #[cfg(feature = "encrypted")]
+    return EncryptionType::Dh;
+    #[cfg(not(feature = "encrypted"))]
+    return EncryptionType::Plain;
+}
+
+/// [EncryptionType] isn't `Clone`, so we copy it by hand.
+fn copy_encryption(encryption: &EncryptionType) -> EncryptionType {
+    match encryption {
+        EncryptionType::Plain => EncryptionType::Plain,
+        EncryptionType::Dh => EncryptionType::Dh,
+    }
+}
+
+/// Connect to the secret service, using a session with the given encryption.
+///
+/// We show most prompts ourselves (see [Prompter]), but the library
+/// is told about the prompt options in case it shows any.
+fn connect(encryption: EncryptionType, prompts: &PromptOptions) -> Result<SecretService> {
+    let ss = match (prompts.never_prompt, prompts.timeout) {
+        (true, _) => SecretService::connect_with_max_prompt_timeout(encryption, 0),
+        (false, Some(timeout)) => {
+            let seconds = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+            SecretService::connect_with_max_prompt_timeout(encryption, seconds.max(1))
+        }
+        (false, None) => SecretService::connect(encryption),
+    };
+    ss.map_err(platform_failure)
+}
+
+const SERVICE_NAME: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const PROMPT_INTERFACE: &str = "org.freedesktop.Secret.Prompt";
+
+/// The timeout for D-Bus calls, which is what the secret service library uses.
+const DBUS_TIMEOUT: Duration = Duration::from_millis(2000);
+
+/// How often we check for the answer to a prompt.
+const PROMPT_POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// A proxy for the secret service on the given connection.
+fn service_proxy(connection: &DbusConnection) -> dbus::blocking::Proxy<'_, &DbusConnection> {
+    connection.with_proxy(SERVICE_NAME, SERVICE_PATH, DBUS_TIMEOUT)
+}
+
 /// Find the secret service collection whose label is the given name.
 ///
 /// The name `default` is treated specially and is interpreted as naming
 /// the default collection regardless of its label (which might be different).
 pub fn get_collection<'a>(ss: &'a SecretService, name: &str) -> Result<Collection<'a>> {
+    let collection = find_collection(ss, name)?;
+    if collection.is_locked().map_err(decode_error)? {
+        collection.unlock().map_err(decode_error)?;
+    }
+    Ok(collection)
+}
+
+/// Find the secret service collection whose label is the given name, without unlocking it.
+fn find_collection<'a>(ss: &'a SecretService, name: &str) -> Result<Collection<'a>> {
     let collection = if name.eq("default") {
         ss.get_default_collection().map_err(decode_error)?
     } else {
@@ -474,9 +1435,6 @@ pub fn get_collection<'a>(ss: &'a SecretService, name: &str) -> Result<Collectio
             .find(|c| c.get_label().map(|l| l.eq(name)).unwrap_or(false));
         found.ok_or(ErrorCode::NoEntry)?
     };
-    if collection.is_locked().map_err(decode_error)? {
-        collection.unlock().map_err(decode_error)?;
-    }
     Ok(collection)
 }
 
@@ -571,6 +1529,23 @@ pub fn decode_error(err: Error) -> ErrorCode {
     }
 }
 
+/// Whether an error means that the connection, or our session on it, can't be used any more.
+fn is_stale(err: &ErrorCode) -> bool {
+    let (ErrorCode::PlatformFailure(err) | ErrorCode::NoStorageAccess(err)) = err else {
+        return false;
+    };
+    match err.downcast_ref::<Error>() {
+        Some(Error::Dbus(err)) => matches!(
+            err.name(),
+            Some(
+                "org.freedesktop.DBus.Error.Disconnected"
+                    | "org.freedesktop.Secret.Error.NoSession"
+            )
+        ),
+        _ => false,
+    }
+}
+
 fn empty_target() -> ErrorCode {
     ErrorCode::Invalid("target".to_string(), "cannot be empty".to_string())
 }
@@ -579,6 +1554,10 @@ fn platform_failure(err: Error) -> ErrorCode {
     ErrorCode::PlatformFailure(wrap(err))
 }
 
+fn dbus_failure(err: dbus::Error) -> ErrorCode {
+    platform_failure(Error::Dbus(err))
+}
+
 fn no_access(err: Error) -> ErrorCode {
     ErrorCode::NoStorageAccess(wrap(err))
 }
@@ -589,11 +1568,43 @@ fn wrap(err: Error) -> Box<dyn std::error::Error + Send + Sync> {
 
 #[cfg(test)]
 mod tests {
-    use crate::credential::CredentialPersistence;
-    use crate::{Entry, Error, tests::generate_random_string};
+    use crate::credential::{
+        CredentialApi, CredentialBuilderApi, CredentialPersistence, StoreStatus,
+    };
+    use crate::{Credential, Entry, Error, Result, testing::generate_random_string};
     use std::collections::HashMap;
 
-    use super::{EncryptionType, SecretService, SsCredential, default_credential_builder};
+    use super::{
+        DEFAULT_PROMPT_TIMEOUT, EncryptionType, PromptOptions, SecretService, SsCredential,
+        SsCredentialBuilder, default_credential_builder,
+    };
+
+    #[cfg(feature = "fake-secret-service")]
+    use crate::fake_secret_service::{FakeSecretService, PromptAction};
+    #[cfg(feature = "fake-secret-service")]
+    use std::time::{Duration, Instant};
+
+    /// Tests that lock collections, or answer prompts, take turns,
+    /// because the fake's prompt setting is shared.
+    #[cfg(feature = "fake-secret-service")]
+    static PROMPTS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    /// Point the tests at a fake secret service, if that feature is on.
+    /// (Otherwise they need a running, unlocked secret service.)
+    ///
+    /// Since the D-Bus library only reads the session bus address once,
+    /// every test that connects must call this before it does.
+    pub(super) fn setup() {
+        #[cfg(feature = "fake-secret-service")]
+        fake();
+    }
+
+    #[cfg(feature = "fake-secret-service")]
+    fn fake() -> &'static FakeSecretService {
+        // SAFETY: every connection made by this crate's tests calls this first,
+        // and nothing else in them reads the environment outside of Rust
+        unsafe { FakeSecretService::install() }.expect("Can't start the fake secret service")
+    }
 
     #[test]
     fn test_persistence() {
@@ -603,13 +1614,52 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn test_probe() {
+        let report = builder().probe();
+        assert_eq!(report.status, StoreStatus::Available, "{report}");
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = default_credential_builder().capabilities();
+        assert!(capabilities.attributes && capabilities.search);
+        assert!(capabilities.empty_service && capabilities.empty_user);
+        assert!(!capabilities.atomic_updates);
+        assert!(capabilities.locking);
+    }
+
+    /// The session encryption used by the tests.
+    /// (The fake only supports plain sessions.)
+    fn encryption() -> EncryptionType {
+        if cfg!(feature = "fake-secret-service") {
+            EncryptionType::Plain
+        } else {
+            super::default_encryption()
+        }
+    }
+
+    /// A builder whose credentials use the tests' session encryption.
+    fn builder() -> SsCredentialBuilder {
+        setup();
+        SsCredentialBuilder::with_encryption(encryption())
+    }
+
+    /// A credential that uses the tests' session encryption.
+    fn credential_new(target: Option<&str>, service: &str, user: &str) -> Result<SsCredential> {
+        setup();
+        let mut credential = SsCredential::new_with_target(target, service, user)?;
+        credential.set_encryption(encryption());
+        Ok(credential)
+    }
+
     fn entry_new(service: &str, user: &str) -> Entry {
-        crate::tests::entry_from_constructor(SsCredential::new_with_target, service, user)
+        crate::testing::entry_from_constructor(credential_new, service, user)
     }
 
     #[test]
     fn test_invalid_parameter() {
-        let credential = SsCredential::new_with_target(Some(""), "service", "user");
+        let credential = credential_new(Some(""), "service", "user");
         assert!(
             matches!(credential, Err(Error::Invalid(_, _))),
             "Created entry with empty target"
@@ -618,37 +1668,37 @@ mod tests {
 
     #[test]
     fn test_empty_service_and_user() {
-        crate::tests::test_empty_service_and_user(entry_new);
+        crate::testing::test_empty_service_and_user(entry_new);
     }
 
     #[test]
     fn test_missing_entry() {
-        crate::tests::test_missing_entry(entry_new);
+        crate::testing::test_missing_entry(entry_new);
     }
 
     #[test]
     fn test_empty_password() {
-        crate::tests::test_empty_password(entry_new);
+        crate::testing::test_empty_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_ascii_password() {
-        crate::tests::test_round_trip_ascii_password(entry_new);
+        crate::testing::test_round_trip_ascii_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_non_ascii_password() {
-        crate::tests::test_round_trip_non_ascii_password(entry_new);
+        crate::testing::test_round_trip_non_ascii_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_random_secret() {
-        crate::tests::test_round_trip_random_secret(entry_new);
+        crate::testing::test_round_trip_random_secret(entry_new);
     }
 
     #[test]
     fn test_update() {
-        crate::tests::test_update(entry_new);
+        crate::testing::test_update(entry_new);
     }
 
     #[test]
@@ -681,9 +1731,10 @@ mod tests {
 
     #[test]
     fn test_get_update_attributes() {
+        setup();
         let name = generate_random_string();
-        let credential = SsCredential::new_with_target(None, &name, &name)
-            .expect("Can't create credential for attribute test");
+        let credential =
+            credential_new(None, &name, &name).expect("Can't create credential for attribute test");
         let create_label = credential.label.clone();
         let entry = Entry::new_with_credential(Box::new(credential));
         assert!(
@@ -740,10 +1791,46 @@ mod tests {
     }
 
     #[test]
-    #[ignore = "can't be run headless, because it needs to prompt"]
+    fn test_search() {
+        setup();
+        let name = generate_random_string();
+        let entry1 = entry_new(&name, "user1");
+        let entry2 = entry_new(&name, "user2");
+        entry1
+            .set_password("search 1")
+            .expect("Can't set password 1");
+        entry2
+            .set_password("search 2")
+            .expect("Can't set password 2");
+        let builder = builder();
+        let spec = HashMap::from([("service", name.as_str())]);
+        let found = builder.search(&spec).expect("Can't search by service");
+        assert_eq!(found.len(), 2, "Wrong number of items found by service");
+        let spec = HashMap::from([("service", name.as_str()), ("user", "user2")]);
+        let found = builder
+            .search(&spec)
+            .expect("Can't search by service and user");
+        assert_eq!(found.len(), 1, "Wrong number of items found by user");
+        let found = Entry::new_with_credential(found.into_iter().next().unwrap());
+        assert_eq!(found.get_password().unwrap(), "search 2");
+        entry1.delete_credential().expect("Can't delete entry 1");
+        entry2.delete_credential().expect("Can't delete entry 2");
+        let spec = HashMap::from([("service", name.as_str())]);
+        let found = builder.search(&spec).expect("Can't search after delete");
+        assert!(found.is_empty(), "Found deleted items");
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(feature = "fake-secret-service"),
+        ignore = "can't be run headless, because it needs to prompt"
+    )]
     fn test_create_new_target_collection() {
+        setup();
+        #[cfg(feature = "fake-secret-service")]
+        let _prompts = PROMPTS.lock().unwrap_or_else(|err| err.into_inner());
         let name = generate_random_string();
-        let credential = SsCredential::new_with_target(Some(&name), &name, &name)
+        let credential = credential_new(Some(&name), &name, &name)
             .expect("Can't create credential for new collection");
         let entry = Entry::new_with_credential(Box::new(credential));
         let password = "password in new collection";
@@ -762,17 +1849,23 @@ mod tests {
     }
 
     #[test]
-    #[ignore = "can't be run headless, because it needs to prompt"]
+    #[cfg_attr(
+        not(feature = "fake-secret-service"),
+        ignore = "can't be run headless, because it needs to prompt"
+    )]
     fn test_separate_targets_dont_interfere() {
+        setup();
+        #[cfg(feature = "fake-secret-service")]
+        let _prompts = PROMPTS.lock().unwrap_or_else(|err| err.into_inner());
         let name1 = generate_random_string();
         let name2 = generate_random_string();
-        let credential1 = SsCredential::new_with_target(Some(&name1), &name1, &name1)
+        let credential1 = credential_new(Some(&name1), &name1, &name1)
             .expect("Can't create credential1 with new collection");
         let entry1 = Entry::new_with_credential(Box::new(credential1));
-        let credential2 = SsCredential::new_with_target(Some(&name2), &name1, &name1)
+        let credential2 = credential_new(Some(&name2), &name1, &name1)
             .expect("Can't create credential2 with new collection");
         let entry2 = Entry::new_with_credential(Box::new(credential2));
-        let entry3 = Entry::new(&name1, &name1).expect("Can't create entry in default collection");
+        let entry3 = entry_new(&name1, &name1);
         let password1 = "password for collection 1";
         let password2 = "password for collection 2";
         let password3 = "password for default collection";
@@ -815,9 +1908,10 @@ mod tests {
 
     #[test]
     fn test_legacy_entry() {
+        setup();
         let name = generate_random_string();
         let pw = "test password";
-        let v3_entry = Entry::new(&name, &name).expect("Can't create v3 entry");
+        let v3_entry = entry_new(&name, &name);
         let _ = v3_entry.get_password().expect_err("Found v3 entry");
         create_v1_entry(&name, pw);
         let password = v3_entry.get_password().expect("Can't find v1 entry");
@@ -835,11 +1929,421 @@ mod tests {
         collection.delete().expect("Can't delete collection");
     }
 
-    fn create_v1_entry(name: &str, password: &str) {
-        use dbus_secret_service::{EncryptionType, SecretService};
+    #[test]
+    fn test_ambiguous_entry() {
+        setup();
+        let name = generate_random_string();
+        let current = credential_new(None, &name, &name)
+            .expect("Can't create credential with default target");
+        let legacy = SsCredential::new_with_no_target(&name, &name)
+            .expect("Can't create credential with no target");
+        let entry = Entry::new_with_credential(Box::new(current.clone()));
+        // duplicate legacy items are found by the fallback search
+        create_item(&legacy, "legacy 1");
+        create_item(&legacy, "legacy 2");
+        assert_ambiguous(&entry, &current, &["legacy 1", "legacy 2"]);
+        // duplicate current items are found by the service-wide search
+        create_item(&current, "current 1");
+        create_item(&current, "current 2");
+        assert_ambiguous(&entry, &current, &["current 1", "current 2"]);
+    }
 
+    #[test]
+    #[cfg(feature = "fake-secret-service")]
+    fn test_locked_collection() {
+        setup();
+        let _prompts = PROMPTS.lock().unwrap_or_else(|err| err.into_inner());
+        let fake = fake();
+        let name = generate_random_string();
+        let credential = credential_new(Some(&name), &name, &name)
+            .expect("Can't create credential for locked collection");
+        let entry = Entry::new_with_credential(Box::new(credential));
+        entry
+            .set_password("locked password")
+            .expect("Can't set password in new collection");
+        let shown = fake.prompts_shown();
+        lock_collection(&name);
+        fake.set_prompt_action(PromptAction::Dismiss);
+        let dismissed = entry.get_password();
+        fake.set_prompt_action(PromptAction::Approve);
+        assert!(
+            matches!(dismissed, Err(Error::NoStorageAccess(_))),
+            "Read a locked item without unlocking it: {dismissed:?}"
+        );
+        let password = entry.get_password().expect("Can't unlock locked item");
+        assert_eq!(password, "locked password");
+        assert_eq!(fake.prompts_shown(), shown + 2, "Wrong number of prompts");
+        entry
+            .delete_credential()
+            .expect("Can't delete unlocked item");
+        delete_collection(&name);
+    }
+
+    #[test]
+    fn test_map_matching_items_can_reenter() {
+        let name = generate_random_string();
+        let credential =
+            credential_new(Some(&name), &name, &name).expect("Can't create credential");
+        credential
+            .set_password("reentered")
+            .expect("Can't set password");
+        let inner = credential.clone();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let outer = credential.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(outer.map_matching_items(move |_| inner.get_password(), true));
+        });
+        let result = receiver
+            .recv_timeout(std::time::Duration::from_secs(10))
+            .expect("Nested call deadlocked");
+        assert_eq!(
+            result.expect("Can't map items"),
+            vec!["reentered".to_string()]
+        );
+        credential
+            .delete_credential()
+            .expect("Can't delete password");
+    }
+
+    #[test]
+    fn test_default_prompts_time_out() {
+        // an unanswered prompt mustn't hold up the worker forever
+        let options = PromptOptions::default();
+        assert_eq!(options.timeout, Some(DEFAULT_PROMPT_TIMEOUT));
+        assert!(!options.never_prompt);
+        assert!(options.window_id.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "fake-secret-service")]
+    fn test_prompt_options() {
+        setup();
+        let _prompts = PROMPTS.lock().unwrap_or_else(|err| err.into_inner());
+        let fake = fake();
+        let mut builder = builder();
+        let name = generate_random_string();
+        let mut build = |options: PromptOptions| {
+            builder.set_prompt_options(options);
+            Entry::new_with_credential(builder.build(Some(&name), &name, &name).unwrap())
+        };
+        let never = build(PromptOptions::default().with_never_prompt(true));
+        assert!(
+            matches!(never.set_password("never"), Err(Error::Locked)),
+            "Created a collection without a prompt"
+        );
+        let windowed = build(PromptOptions::default().with_window_id("x11:2a"));
+        windowed
+            .set_password("windowed")
+            .expect("Can't set password");
+        lock_collection(&name);
+        let shown = fake.prompts_shown();
+        assert!(matches!(never.get_password(), Err(Error::Locked)));
+        assert_eq!(fake.prompts_shown(), shown, "Prompted when told not to");
+        let timed = build(PromptOptions::default().with_timeout(Duration::from_millis(200)));
+        fake.set_prompt_action(PromptAction::Ignore);
+        let start = Instant::now();
+        let timed_out = timed.get_password();
+        fake.set_prompt_action(PromptAction::Approve);
+        assert!(
+            matches!(timed_out, Err(Error::NoStorageAccess(_))),
+            "Unanswered prompt didn't time out: {timed_out:?}"
+        );
+        assert!(
+            start.elapsed() < Duration::from_secs(1),
+            "Timeout was too long"
+        );
+        assert_eq!(windowed.get_password().unwrap(), "windowed");
+        let windows = fake.prompt_windows();
+        assert_eq!(windows[windows.len() - 1], "x11:2a");
+        assert_eq!(never.get_password().unwrap(), "windowed");
+        windowed.delete_credential().expect("Can't delete password");
+        delete_collection(&name);
+    }
+
+    #[test]
+    #[cfg(feature = "fake-secret-service")]
+    fn test_lock_and_unlock() {
+        setup();
+        let _prompts = PROMPTS.lock().unwrap_or_else(|err| err.into_inner());
+        let fake = fake();
+        let name = generate_random_string();
+        let credential =
+            credential_new(Some(&name), &name, &name).expect("Can't create credential");
+        let entry = Entry::new_with_credential(Box::new(credential));
+        assert!(matches!(entry.is_locked(), Err(Error::NoEntry)));
+        entry.set_password("lockable").expect("Can't set password");
+        assert!(!entry.is_locked().expect("Can't check lock"));
+        entry.lock().expect("Can't lock entry");
+        assert!(entry.is_locked().expect("Can't check lock"));
+        let mut builder = builder();
+        builder.set_prompt_options(PromptOptions::default().with_never_prompt(true));
+        let never = Entry::new_with_credential(builder.build(Some(&name), &name, &name).unwrap());
+        assert!(matches!(never.get_password(), Err(Error::Locked)));
+        assert!(matches!(never.unlock(), Err(Error::Locked)));
+        let shown = fake.prompts_shown();
+        entry.unlock().expect("Can't unlock entry");
+        assert_eq!(fake.prompts_shown(), shown + 1, "Wrong number of prompts");
+        assert!(!entry.is_locked().expect("Can't check lock"));
+        entry.unlock().expect("Can't unlock unlocked entry");
+        assert_eq!(fake.prompts_shown(), shown + 1, "Prompted when unlocked");
+        assert_eq!(never.get_password().unwrap(), "lockable");
+        entry.delete_credential().expect("Can't delete password");
+        entry.lock().expect("Can't lock empty collection");
+        assert!(entry.is_locked().expect("Can't check lock"));
+        entry.unlock().expect("Can't unlock empty collection");
+        delete_collection(&name);
+    }
+
+    #[test]
+    fn test_connection_reuse() {
+        setup();
+        let builder = builder();
+        let name = generate_random_string();
+        let entry = Entry::new_with_credential(
+            builder
+                .build(None, &name, &name)
+                .expect("Can't build credential"),
+        );
+        entry.set_password("reused").expect("Can't set password");
+        assert_eq!(entry.get_password().expect("Can't get password"), "reused");
+        let spec = HashMap::from([("service", name.as_str())]);
+        let found = builder.search(&spec).expect("Can't search");
+        let found = Entry::new_with_credential(found.into_iter().next().expect("Nothing found"));
+        found
+            .delete_credential()
+            .expect("Can't delete found credential");
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        assert_eq!(builder.connection.connects(), 1, "Connection wasn't reused");
+    }
+
+    #[test]
+    fn test_get_secrets_many() {
+        setup();
+        // other tests mustn't make GetSecrets calls while these are counted
+        #[cfg(feature = "fake-secret-service")]
+        let _prompts = PROMPTS.lock().unwrap_or_else(|err| err.into_inner());
+        let builder = builder();
+        let name = generate_random_string();
+        let mut credentials: Vec<Box<Credential>> = (0..3)
+            .map(|i| builder.build(None, &name, &format!("{name}-{i}")).unwrap())
+            .collect();
+        credentials[0]
+            .set_secret(b"secret0")
+            .expect("Can't set secret");
+        credentials[2]
+            .set_secret(b"secret2")
+            .expect("Can't set secret");
+        let mock = crate::mock::default_credential_builder();
+        credentials.push(mock.build(None, &name, &name).unwrap());
+        credentials[3].set_secret(b"mock").unwrap();
+        let credentials: Vec<&Credential> = credentials.iter().map(|c| c.as_ref()).collect();
+        let check = |results: Vec<Result<Vec<u8>>>| {
+            assert_eq!(results.len(), 4);
+            assert_eq!(results[0].as_ref().unwrap(), b"secret0");
+            assert!(matches!(results[1], Err(Error::NoEntry)));
+            assert_eq!(results[2].as_ref().unwrap(), b"secret2");
+            assert_eq!(results[3].as_ref().unwrap(), b"mock");
+        };
+        #[cfg(feature = "fake-secret-service")]
+        let calls = fake().get_secrets_calls();
+        check(builder.get_secrets_many(&credentials));
+        #[cfg(feature = "fake-secret-service")]
+        assert_eq!(
+            fake().get_secrets_calls(),
+            calls + 1,
+            "Secrets weren't batched"
+        );
+        // a lost batch session is replaced
+        #[cfg(feature = "fake-secret-service")]
+        fake().end_sessions();
+        check(builder.get_secrets_many(&credentials));
+        #[cfg(feature = "fake-secret-service")]
+        assert_eq!(
+            fake().get_secrets_calls(),
+            calls + 2,
+            "Secrets weren't batched"
+        );
+        credentials[0]
+            .delete_credential()
+            .expect("Can't delete credential");
+        credentials[2]
+            .delete_credential()
+            .expect("Can't delete credential");
+    }
+
+    #[test]
+    #[cfg(feature = "fake-secret-service")]
+    fn test_get_secrets_many_per_builder() {
+        setup();
+        let _prompts = PROMPTS.lock().unwrap_or_else(|err| err.into_inner());
+        let fake = fake();
+        let name = generate_random_string();
+        let prompting = builder();
+        let mut never = builder();
+        never.set_prompt_options(PromptOptions::default().with_never_prompt(true));
+        let entry1 = Entry::new_with_credential(prompting.build(Some(&name), &name, "1").unwrap());
+        let entry2 = Entry::new_with_credential(never.build(Some(&name), &name, "2").unwrap());
+        entry1
+            .set_password("secret1")
+            .expect("Can't set password 1");
+        entry2
+            .set_password("secret2")
+            .expect("Can't set password 2");
+        let check = |results: Vec<Result<Vec<u8>>>| {
+            assert!(matches!(results[0], Err(Error::Locked)), "{results:?}");
+            assert_eq!(results[1].as_ref().unwrap(), b"secret1");
+        };
+        lock_collection(&name);
+        let shown = fake.prompts_shown();
+        let calls = fake.get_secrets_calls();
+        check(Entry::get_secrets_many(&[&entry2, &entry1]));
+        assert_eq!(fake.prompts_shown(), shown + 1, "Wrong number of prompts");
+        // the never-prompting group fails before its secrets are fetched
+        assert_eq!(
+            fake.get_secrets_calls(),
+            calls + 1,
+            "Secrets weren't batched"
+        );
+        lock_collection(&name);
+        let other = builder();
+        let credentials: Vec<&Credential> = [&entry2, &entry1]
+            .iter()
+            .map(|e| e.inner.as_ref())
+            .collect();
+        check(other.get_secrets_many(&credentials));
+        assert_eq!(other.connection.connects(), 0, "Used the wrong connection");
+        assert_eq!(
+            fake.get_secrets_calls(),
+            calls + 2,
+            "Secrets weren't batched"
+        );
+        entry1.delete_credential().expect("Can't delete password 1");
+        entry2.delete_credential().expect("Can't delete password 2");
+        delete_collection(&name);
+    }
+
+    #[test]
+    fn test_encryption() {
+        setup();
+        let builder = SsCredentialBuilder::default();
+        assert_eq!(builder.encryption(), super::default_encryption());
+        let builder = SsCredentialBuilder::with_encryption(EncryptionType::Plain);
+        let name = generate_random_string();
+        let credential = builder.build(None, &name, &name).unwrap();
+        let credential: &SsCredential = credential.as_any().downcast_ref().unwrap();
+        assert_eq!(credential.encryption(), EncryptionType::Plain);
+        let mut credential = credential.clone();
+        credential
+            .set_password("plain")
+            .expect("Can't set password");
+        credential.set_encryption(EncryptionType::Dh);
+        assert_eq!(credential.encryption(), EncryptionType::Dh);
+        // the fake only supports plain sessions
+        #[cfg(feature = "fake-secret-service")]
+        {
+            assert!(matches!(
+                credential.get_password(),
+                Err(Error::PlatformFailure(_))
+            ));
+            let builder = SsCredentialBuilder::with_encryption(EncryptionType::Dh);
+            assert_eq!(builder.probe().status, StoreStatus::Unreachable);
+            credential.set_encryption(EncryptionType::Plain);
+        }
+        assert_eq!(
+            credential.get_password().expect("Can't get password"),
+            "plain"
+        );
+        credential
+            .delete_credential()
+            .expect("Can't delete password");
+    }
+
+    #[test]
+    fn test_conformance() {
+        setup();
+        crate::testing::run_conformance_suite(&builder());
+    }
+
+    #[test]
+    fn test_worker_survives_panic() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        entry.set_password("survivor").expect("Can't set password");
+        let credential: &SsCredential = entry
+            .get_credential()
+            .downcast_ref()
+            .expect("Not a secret service credential");
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            credential.map_matching_items(|_| -> crate::Result<()> { panic!("in worker") }, true)
+        }));
+        assert!(panicked.is_err(), "Panic wasn't passed on to the caller");
+        assert_eq!(
+            entry
+                .get_password()
+                .expect("Can't get password after panic"),
+            "survivor"
+        );
+        entry.delete_credential().expect("Can't delete password");
+    }
+
+    #[test]
+    #[cfg(feature = "fake-secret-service")]
+    fn test_reconnect() {
+        setup();
+        let builder = builder();
+        let name = generate_random_string();
+        let entry = Entry::new_with_credential(
+            builder
+                .build(None, &name, &name)
+                .expect("Can't build credential"),
+        );
+        entry
+            .set_password("reconnected")
+            .expect("Can't set password");
+        fake().end_sessions();
+        assert_eq!(
+            entry
+                .get_password()
+                .expect("Can't get password after restart"),
+            "reconnected"
+        );
+        entry.delete_credential().expect("Can't delete password");
+        assert_eq!(builder.connection.connects(), 2, "Didn't reconnect once");
+    }
+
+    fn assert_ambiguous(entry: &Entry, credential: &SsCredential, passwords: &[&str]) {
+        match entry.get_password() {
+            Err(Error::Ambiguous(found)) => assert_eq!(found.len(), passwords.len()),
+            other => panic!("Duplicate items were not ambiguous: {other:?}"),
+        }
+        let mut found = credential
+            .get_all_passwords()
+            .expect("Can't get all passwords");
+        found.sort();
+        assert_eq!(found, passwords);
+        credential
+            .delete_all_passwords()
+            .expect("Can't delete all passwords");
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    }
+
+    #[cfg(feature = "fake-secret-service")]
+    fn lock_collection(name: &str) {
+        let ss =
+            SecretService::connect(EncryptionType::Plain).expect("Can't connect to secret service");
+        let collection = super::get_collection(&ss, name).expect("Can't find collection to lock");
+        collection.lock().expect("Can't lock collection");
+    }
+
+    fn create_v1_entry(name: &str, password: &str) {
         let cred = SsCredential::new_with_no_target(name, name)
             .expect("Can't create credential with no target");
+        create_item(&cred, password);
+    }
+
+    /// Add an item for the credential to the default collection,
+    /// even if there already is one.
+    fn create_item(cred: &SsCredential, password: &str) {
         let ss =
             SecretService::connect(EncryptionType::Plain).expect("Can't connect to secret service");
         let collection = ss
@@ -850,9 +2354,9 @@ mod tests {
                 cred.label.as_str(),
                 cred.all_attributes(),
                 password.as_bytes(),
-                true, // replace
+                false, // replace
                 "text/plain",
             )
-            .expect("Can't create item with no target in default collection");
+            .expect("Can't create item in default collection");
     }
 }
@@ -0,0 +1,639 @@
+/*!
+
+# Conformance tests for credential stores
+
+This module contains the tests that this crate runs against each of
+its credential stores, so that the authors of other stores can run them
+against theirs.  It's available in tests of this crate and,
+when the `testing` feature is specified, to clients.
+
+The simplest way to test a store is to pass its credential builder to
+[run_conformance_suite], which runs every test that applies to the store
+(as determined by the builder's [capabilities](crate::credential::CredentialBuilderApi::capabilities)),
+including tests that use entries from several threads at once.  Each test
+panics (with a message saying what went wrong) if the store misbehaves.
+```rust
+keyring::testing::run_conformance_suite(keyring::mock::default_credential_builder().as_ref());
+```
+
+The individual tests are also available, for stores that are expected
+to fail some of them.  (For example, the Windows store doesn't reliably
+serialize accesses to the same credential from different threads.)
+Most of them take a function that makes an entry for a given service
+and user; the threading tests take a credential builder.
+
+The tests use randomly generated service and user names, so they
+won't conflict with existing credentials, and they delete the
+credentials they create, unless they fail.
+
+## Temporary entries
+
+Your own tests can use a [TempEntry] rather than an [Entry] to make sure
+they don't leave credentials behind.  A temporary entry has a uniquely generated
+service name, and deletes its credential when it's dropped, even if the
+test that created it panics:
+```rust
+# use keyring::testing::TempEntry;
+# keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+let entry = TempEntry::new().unwrap();
+entry.set_password("temporary").unwrap();
+assert_eq!(entry.get_password().unwrap(), "temporary");
+// the credential is deleted here, when the entry is dropped
+```
+
+If a test run is interrupted (so the temporary entries aren't dropped),
+its credentials are left in the store.  All temporary entries use the user
+name [TEMP_ENTRY_USER], so in stores that can search, a later run can
+find and delete those leftover credentials by calling [purge_temp_entries].
+ */
+use std::collections::HashMap;
+
+use log::{debug, warn};
+
+use super::credential::{CredentialApi, CredentialBuilder};
+use super::{Entry, Error, Result};
+
+/// Create a platform-specific credential given the constructor, service, and user
+pub fn entry_from_constructor<F, T>(f: F, service: &str, user: &str) -> Entry
+where
+    F: FnOnce(Option<&str>, &str, &str) -> Result<T>,
+    T: 'static + CredentialApi + Send + Sync,
+{
+    match f(None, service, user) {
+        Ok(credential) => Entry::new_with_credential(Box::new(credential)),
+        Err(err) => {
+            panic!("Couldn't create entry (service: {service}, user: {user}): {err:?}")
+        }
+    }
+}
+
+/// Create a platform-specific credential given the constructor, service, user, and attributes
+pub fn entry_from_constructor_and_attributes<F, T>(
+    f: F,
+    service: &str,
+    user: &str,
+    attrs: &HashMap<&str, &str>,
+) -> Entry
+where
+    F: FnOnce(Option<&str>, &str, &str, &HashMap<&str, &str>) -> Result<T>,
+    T: 'static + CredentialApi + Send + Sync,
+{
+    match f(None, service, user, attrs) {
+        Ok(credential) => Entry::new_with_credential(Box::new(credential)),
+        Err(err) => {
+            panic!("Couldn't create entry (service: {service}, user: {user}): {err:?}")
+        }
+    }
+}
+
+fn test_round_trip_no_delete(case: &str, entry: &Entry, in_pass: &str) {
+    entry
+        .set_password(in_pass)
+        .unwrap_or_else(|err| panic!("Can't set password for {case}: {err:?}"));
+    let out_pass = entry
+        .get_password()
+        .unwrap_or_else(|err| panic!("Can't get password for {case}: {err:?}"));
+    assert_eq!(
+        in_pass, out_pass,
+        "Passwords don't match for {case}: set='{in_pass}', get='{out_pass}'",
+    )
+}
+
+/// A basic round-trip unit test given an entry and a password.
+pub fn test_round_trip(case: &str, entry: &Entry, in_pass: &str) {
+    test_round_trip_no_delete(case, entry, in_pass);
+    entry
+        .delete_credential()
+        .unwrap_or_else(|err| panic!("Can't delete password for {case}: {err:?}"));
+    let password = entry.get_password();
+    assert!(
+        matches!(password, Err(Error::NoEntry)),
+        "Read deleted password for {case}",
+    );
+}
+
+/// A basic round-trip unit test given an entry and a password.
+pub fn test_round_trip_secret(case: &str, entry: &Entry, in_secret: &[u8]) {
+    entry
+        .set_secret(in_secret)
+        .unwrap_or_else(|err| panic!("Can't set secret for {case}: {err:?}"));
+    let out_secret = entry
+        .get_secret()
+        .unwrap_or_else(|err| panic!("Can't get secret for {case}: {err:?}"));
+    assert_eq!(
+        in_secret, &out_secret,
+        "Passwords don't match for {case}: set='{in_secret:?}', get='{out_secret:?}'",
+    );
+    entry
+        .delete_credential()
+        .unwrap_or_else(|err| panic!("Can't delete password for {case}: {err:?}"));
+    let password = entry.get_secret();
+    assert!(
+        matches!(password, Err(Error::NoEntry)),
+        "Read deleted password for {case}",
+    );
+}
+
+/// When tests fail, they leave keys behind, and those keys
+/// have to be cleaned up before the tests can be run again
+/// in order to avoid bad results.  So it's a lot easier just
+/// to have tests use a random string for key names to avoid
+/// the conflicts, and then do any needed cleanup once everything
+/// is working correctly.  So we export this function for tests to use.
+pub fn generate_random_string_of_len(len: usize) -> String {
+    use fastrand;
+    use std::iter::repeat_with;
+    repeat_with(fastrand::alphanumeric).take(len).collect()
+}
+
+/// A random alphanumeric string of length 30, for use as a service or user name.
+pub fn generate_random_string() -> String {
+    generate_random_string_of_len(30)
+}
+
+/// A random byte string of the given length, for use as a secret.
+pub fn generate_random_bytes_of_len(len: usize) -> Vec<u8> {
+    use fastrand;
+    use std::iter::repeat_with;
+    repeat_with(|| fastrand::u8(..)).take(len).collect()
+}
+
+/// Check that entries with an empty service or user (or both) round-trip passwords.
+pub fn test_empty_service_and_user<F>(f: F)
+where
+    F: Fn(&str, &str) -> Entry,
+{
+    let name = generate_random_string();
+    let in_pass = "doesn't matter";
+    test_round_trip("empty user", &f(&name, ""), in_pass);
+    test_round_trip("empty service", &f("", &name), in_pass);
+    test_round_trip("empty service & user", &f("", ""), in_pass);
+}
+
+/// Check that an entry for a credential that doesn't exist has no password.
+pub fn test_missing_entry<F>(f: F)
+where
+    F: FnOnce(&str, &str) -> Entry,
+{
+    let name = generate_random_string();
+    let entry = f(&name, &name);
+    assert!(
+        matches!(entry.get_password(), Err(Error::NoEntry)),
+        "Missing entry has password"
+    )
+}
+
+/// Check that an empty password round-trips.
+pub fn test_empty_password<F>(f: F)
+where
+    F: FnOnce(&str, &str) -> Entry,
+{
+    let name = generate_random_string();
+    let entry = f(&name, &name);
+    test_round_trip("empty password", &entry, "");
+}
+
+/// Check that an ASCII password round-trips.
+pub fn test_round_trip_ascii_password<F>(f: F)
+where
+    F: FnOnce(&str, &str) -> Entry,
+{
+    let name = generate_random_string();
+    let entry = f(&name, &name);
+    test_round_trip("ascii password", &entry, "test ascii password");
+}
+
+/// Check that a non-ASCII password round-trips.
+pub fn test_round_trip_non_ascii_password<F>(f: F)
+where
+    F: FnOnce(&str, &str) -> Entry,
+{
+    let name = generate_random_string();
+    let entry = f(&name, &name);
+    test_round_trip("non-ascii password", &entry, "このきれいな花は桜です");
+}
+
+/// Check that a random binary secret round-trips.
+pub fn test_round_trip_random_secret<F>(f: F)
+where
+    F: FnOnce(&str, &str) -> Entry,
+{
+    let name = generate_random_string();
+    let entry = f(&name, &name);
+    let secret = generate_random_bytes_of_len(24);
+    test_round_trip_secret("non-ascii password", &entry, secret.as_slice());
+}
+
+/// Check that a password can be replaced.
+pub fn test_update<F>(f: F)
+where
+    F: FnOnce(&str, &str) -> Entry,
+{
+    let name = generate_random_string();
+    let entry = f(&name, &name);
+    test_round_trip_no_delete("initial ascii password", &entry, "test ascii password");
+    test_round_trip(
+        "updated non-ascii password",
+        &entry,
+        "このきれいな花は桜です",
+    );
+}
+
+/// Check that a store without attributes reports none, and ignores updates.
+pub fn test_noop_get_update_attributes<F>(f: F)
+where
+    F: FnOnce(&str, &str) -> Entry,
+{
+    let name = generate_random_string();
+    let entry = f(&name, &name);
+    assert!(
+        matches!(entry.get_attributes(), Err(Error::NoEntry)),
+        "Read missing credential in attribute test",
+    );
+    let mut map: HashMap<&str, &str> = HashMap::new();
+    map.insert("test attribute name", "test attribute value");
+    assert!(
+        matches!(entry.update_attributes(&map), Err(Error::NoEntry)),
+        "Updated missing credential in attribute test",
+    );
+    // create the credential and test again
+    entry
+        .set_password("test password for attributes")
+        .unwrap_or_else(|err| panic!("Can't set password for attribute test: {err:?}"));
+    match entry.get_attributes() {
+        Err(err) => panic!("Couldn't get attributes: {err:?}"),
+        Ok(attrs) if attrs.is_empty() => {}
+        Ok(attrs) => panic!("Unexpected attributes: {attrs:?}"),
+    }
+    assert!(
+        matches!(entry.update_attributes(&map), Ok(())),
+        "Couldn't update attributes in attribute test",
+    );
+    match entry.get_attributes() {
+        Err(err) => panic!("Couldn't get attributes after update: {err:?}"),
+        Ok(attrs) if attrs.is_empty() => {}
+        Ok(attrs) => panic!("Unexpected attributes after update: {attrs:?}"),
+    }
+    entry
+        .delete_credential()
+        .unwrap_or_else(|err| panic!("Can't delete credential for attribute test: {err:?}"));
+    assert!(
+        matches!(entry.get_attributes(), Err(Error::NoEntry)),
+        "Read deleted credential in attribute test",
+    );
+}
+
+/// Run every conformance test that applies to a credential builder's store.
+///
+/// Which tests apply is determined by the builder's
+/// [capabilities](crate::credential::CredentialBuilderApi::capabilities).
+/// See the module docs for details.
+pub fn run_conformance_suite(builder: &CredentialBuilder) {
+    let capabilities = builder.capabilities();
+    let entry_new = |service: &str, user: &str| entry_from_builder(builder, service, user);
+    test_missing_entry(entry_new);
+    test_empty_password(entry_new);
+    test_round_trip_ascii_password(entry_new);
+    test_round_trip_non_ascii_password(entry_new);
+    if capabilities.binary_secrets {
+        test_round_trip_random_secret(entry_new);
+    }
+    test_update(entry_new);
+    if capabilities.empty_service && capabilities.empty_user {
+        test_empty_service_and_user(entry_new);
+    }
+    if !capabilities.attributes {
+        test_noop_get_update_attributes(entry_new);
+    }
+    test_set_secret_if(entry_new);
+    if capabilities.search {
+        test_search(builder);
+    }
+    test_create_then_move(builder);
+    test_simultaneous_create_then_move(builder);
+    test_create_set_then_move(builder);
+    test_simultaneous_create_set_then_move(builder);
+    test_simultaneous_independent_create_set(builder);
+    test_multiple_create_delete_single_thread(builder);
+    test_simultaneous_multiple_create_delete_single_thread(builder);
+}
+
+/// Create an entry with the given builder, service, and user.
+pub fn entry_from_builder(builder: &CredentialBuilder, service: &str, user: &str) -> Entry {
+    match builder.build(None, service, user) {
+        Ok(credential) => Entry::new_with_credential(credential),
+        Err(err) => {
+            panic!("Couldn't create entry (service: {service}, user: {user}): {err:?}")
+        }
+    }
+}
+
+/// Check that conditional sets succeed only when the current secret is the expected one.
+pub fn test_set_secret_if<F>(f: F)
+where
+    F: FnOnce(&str, &str) -> Entry,
+{
+    let name = generate_random_string();
+    let entry = f(&name, &name);
+    assert!(
+        matches!(
+            entry.set_secret_if(Some(b"old"), b"new"),
+            Err(Error::Conflict)
+        ),
+        "Conditional set of missing credential succeeded",
+    );
+    entry
+        .set_secret_if(None, b"old")
+        .unwrap_or_else(|err| panic!("Can't create credential conditionally: {err:?}"));
+    assert!(
+        matches!(entry.set_secret_if(None, b"new"), Err(Error::Conflict)),
+        "Conditional create of existing credential succeeded",
+    );
+    entry
+        .set_secret_if(Some(b"old"), b"new")
+        .unwrap_or_else(|err| panic!("Can't update credential conditionally: {err:?}"));
+    let secret = entry
+        .get_secret()
+        .unwrap_or_else(|err| panic!("Can't get conditionally set secret: {err:?}"));
+    assert_eq!(secret, b"new", "Conditional update wasn't made");
+    entry
+        .delete_credential()
+        .unwrap_or_else(|err| panic!("Can't delete conditionally set credential: {err:?}"));
+}
+
+/// Check that a search finds a credential by its service and user.
+pub fn test_search(builder: &CredentialBuilder) {
+    let name = generate_random_string();
+    let entry = entry_from_builder(builder, &name, &name);
+    entry
+        .set_password("test password for search")
+        .unwrap_or_else(|err| panic!("Can't set password for search test: {err:?}"));
+    let spec = HashMap::from([("service", name.as_str()), ("user", name.as_str())]);
+    let found = builder
+        .search(&spec)
+        .unwrap_or_else(|err| panic!("Can't search for credential: {err:?}"));
+    assert_eq!(found.len(), 1, "Search found {} credentials", found.len());
+    let password = Entry::new_with_credential(found.into_iter().next().unwrap())
+        .get_password()
+        .unwrap_or_else(|err| panic!("Can't get password of found credential: {err:?}"));
+    assert_eq!(
+        password, "test password for search",
+        "Found wrong credential"
+    );
+    entry
+        .delete_credential()
+        .unwrap_or_else(|err| panic!("Can't delete credential for search test: {err:?}"));
+}
+
+/// Set, get, and delete passwords on an entry, checking each step.
+fn test_set_get_delete(entry: &Entry, passwords: &[&str]) {
+    for password in passwords {
+        entry
+            .set_password(password)
+            .unwrap_or_else(|err| panic!("Can't set password {password}: {err:?}"));
+        let stored = entry
+            .get_password()
+            .unwrap_or_else(|err| panic!("Can't get password {password}: {err:?}"));
+        assert_eq!(&stored, password, "Retrieved and set passwords don't match");
+    }
+    entry
+        .delete_credential()
+        .unwrap_or_else(|err| panic!("Can't delete password: {err:?}"));
+    assert!(
+        matches!(entry.get_password(), Err(Error::NoEntry)),
+        "Able to read a deleted password"
+    );
+}
+
+/// Check that an entry created on one thread can be used on another.
+pub fn test_create_then_move(builder: &CredentialBuilder) {
+    let name = generate_random_string();
+    let entry = entry_from_builder(builder, &name, &name);
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            test_set_get_delete(&entry, &["test ascii password", "このきれいな花は桜です"])
+        });
+    });
+}
+
+/// Check that entries created on one thread can be used on several others at once.
+pub fn test_simultaneous_create_then_move(builder: &CredentialBuilder) {
+    let base = generate_random_string();
+    std::thread::scope(|scope| {
+        for i in 0..10 {
+            let name = format!("{base}-{i}");
+            let entry = entry_from_builder(builder, &name, &name);
+            scope.spawn(move || test_set_get_delete(&entry, &[name.as_str()]));
+        }
+    });
+}
+
+/// Check that a password set on one thread can be read on another.
+pub fn test_create_set_then_move(builder: &CredentialBuilder) {
+    let name = generate_random_string();
+    let entry = entry_from_builder(builder, &name, &name);
+    entry
+        .set_password(&name)
+        .unwrap_or_else(|err| panic!("Can't set password: {err:?}"));
+    std::thread::scope(|scope| {
+        scope.spawn(move || test_set_get_delete(&entry, &[name.as_str()]));
+    });
+}
+
+/// Check that passwords set on one thread can be read on several others at once.
+pub fn test_simultaneous_create_set_then_move(builder: &CredentialBuilder) {
+    let base = generate_random_string();
+    std::thread::scope(|scope| {
+        for i in 0..10 {
+            let name = format!("{base}-{i}");
+            let entry = entry_from_builder(builder, &name, &name);
+            entry
+                .set_password(&name)
+                .unwrap_or_else(|err| panic!("Can't set password: {err:?}"));
+            scope.spawn(move || test_set_get_delete(&entry, &[name.as_str()]));
+        }
+    });
+}
+
+/// Check that several threads can create and use their own entries at once.
+pub fn test_simultaneous_independent_create_set(builder: &CredentialBuilder) {
+    let base = generate_random_string();
+    std::thread::scope(|scope| {
+        for i in 0..10 {
+            let name = format!("{base}-{i}");
+            scope.spawn(move || {
+                let entry = entry_from_builder(builder, &name, &name);
+                test_set_get_delete(&entry, &[name.as_str()]);
+            });
+        }
+    });
+}
+
+/// Check that a credential can be created and deleted repeatedly in quick succession.
+pub fn test_multiple_create_delete_single_thread(builder: &CredentialBuilder) {
+    let name = generate_random_string();
+    let entry = entry_from_builder(builder, &name, &name);
+    for _ in 0..10 {
+        test_set_get_delete(&entry, &[name.as_str()]);
+    }
+}
+
+/// Check that several threads can each create and delete a credential repeatedly at once.
+pub fn test_simultaneous_multiple_create_delete_single_thread(builder: &CredentialBuilder) {
+    let base = generate_random_string();
+    std::thread::scope(|scope| {
+        for i in 0..10 {
+            let name = format!("{base}-{i}");
+            scope.spawn(move || {
+                let entry = entry_from_builder(builder, &name, &name);
+                for _ in 0..10 {
+                    test_set_get_delete(&entry, &[name.as_str()]);
+                }
+            });
+        }
+    });
+}
+
+/// The user name of every [TempEntry], which marks their credentials as temporary.
+pub const TEMP_ENTRY_USER: &str = "keyring-rs-temp-entry";
+
+/// The prefix of the service name of every [TempEntry].
+pub const TEMP_ENTRY_SERVICE_PREFIX: &str = "keyring-rs-temp-";
+
+/// An entry that deletes its credential when it's dropped.
+///
+/// Temporary entries dereference to [Entry], so they have all the usual
+/// entry methods.  See the module docs for details.
+#[derive(Debug)]
+pub struct TempEntry {
+    entry: Entry,
+    service: String,
+}
+
+impl TempEntry {
+    /// Create a temporary entry with a unique service name,
+    /// using the default credential builder.
+    pub fn new() -> Result<Self> {
+        let service = temp_entry_service();
+        let entry = Entry::new(&service, TEMP_ENTRY_USER)?;
+        Ok(Self { entry, service })
+    }
+
+    /// Create a temporary entry with a unique service name, using the given builder.
+    pub fn new_with_builder(builder: &CredentialBuilder) -> Result<Self> {
+        let service = temp_entry_service();
+        let credential = builder.build(None, &service, TEMP_ENTRY_USER)?;
+        Ok(Self {
+            entry: Entry::new_with_credential(credential),
+            service,
+        })
+    }
+
+    /// The (unique) service name of this entry.
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// The user name of this entry, which is always [TEMP_ENTRY_USER].
+    pub fn user(&self) -> &str {
+        TEMP_ENTRY_USER
+    }
+}
+
+impl std::ops::Deref for TempEntry {
+    type Target = Entry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
+
+impl Drop for TempEntry {
+    /// Delete the entry's credential, if there is one.
+    ///
+    /// Since this can't fail, errors are logged rather than returned.
+    fn drop(&mut self) {
+        match self.entry.delete_credential() {
+            Ok(()) => debug!("deleted temporary credential for {}", self.service),
+            Err(Error::NoEntry) => {}
+            Err(err) => warn!(
+                "couldn't delete temporary credential for {}: {err}",
+                self.service
+            ),
+        }
+    }
+}
+
+/// A unique service name for a temporary entry.
+fn temp_entry_service() -> String {
+    format!("{TEMP_ENTRY_SERVICE_PREFIX}{}", generate_random_string())
+}
+
+/// Delete the credentials left behind by temporary entries in the given builder's store.
+///
+/// Returns the number of credentials deleted.  Returns a
+/// [NotSupportedByStore](Error::NotSupportedByStore) error if the store
+/// can't search for credentials.
+pub fn purge_temp_entries(builder: &CredentialBuilder) -> Result<usize> {
+    let spec = HashMap::from([("user", TEMP_ENTRY_USER)]);
+    let mut count = 0;
+    for credential in builder.search(&spec)? {
+        match credential.delete_credential() {
+            Ok(()) => count += 1,
+            Err(Error::NoEntry) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    debug!("purged {count} temporary credentials");
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TEMP_ENTRY_USER, TempEntry, purge_temp_entries};
+    use crate::Error;
+    use crate::mock::MockStore;
+
+    #[test]
+    fn test_temp_entry() {
+        let store = MockStore::new();
+        let builder = store.credential_builder();
+        let entry = TempEntry::new_with_builder(builder.as_ref()).unwrap();
+        let other = TempEntry::new_with_builder(builder.as_ref()).unwrap();
+        assert_ne!(entry.service(), other.service());
+        assert_eq!(entry.user(), TEMP_ENTRY_USER);
+        entry.set_password("temporary").unwrap();
+        drop(other);
+        assert_eq!(store.snapshot().len(), 1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _entry = entry;
+            panic!("test failed");
+        }));
+        assert!(result.is_err());
+        assert!(store.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_purge() {
+        let store = MockStore::new();
+        let builder = store.credential_builder();
+        let entry = TempEntry::new_with_builder(builder.as_ref()).unwrap();
+        entry.set_password("left behind").unwrap();
+        std::mem::forget(entry);
+        store.seed(
+            None,
+            "keyring-rs-temp-other",
+            TEMP_ENTRY_USER,
+            b"left behind",
+        );
+        store.seed(None, "service", "user", b"not temporary");
+        assert_eq!(purge_temp_entries(builder.as_ref()).unwrap(), 2);
+        assert_eq!(store.snapshot().len(), 1);
+        assert_eq!(purge_temp_entries(builder.as_ref()).unwrap(), 0);
+        let builder = crate::credential::nop_credential_builder();
+        assert!(matches!(
+            purge_temp_entries(builder.as_ref()),
+            Err(Error::NotSupportedByStore(_))
+        ));
+    }
+}
@@ -51,7 +51,9 @@ use windows_sys::Win32::Security::Credentials::{
     CREDENTIAL_ATTRIBUTEW, CREDENTIALW, CredDeleteW, CredFree, CredReadW, CredWriteW,
 };
 
-use super::credential::{Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi};
+use super::credential::{
+    Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi, CredentialCapabilities,
+};
 use super::error::{Error as ErrorCode, Result};
 
 /// The representation of a Windows Generic credential.
@@ -401,6 +403,24 @@ impl CredentialBuilderApi for WinCredentialBuilder {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    /// Windows credentials have attributes and binary secrets, and accept empty names.
+    ///
+    /// Note that the service name is limited indirectly, because it's part
+    /// of the default target name, and that passwords are stored as UTF-16,
+    /// so they can be at most half as long as the secret limit.
+    fn capabilities(&self) -> CredentialCapabilities {
+        CredentialCapabilities {
+            attributes: true,
+            binary_secrets: true,
+            empty_service: true,
+            empty_user: true,
+            max_target_len: Some(CRED_MAX_GENERIC_TARGET_NAME_LENGTH as usize),
+            max_user_len: Some(CRED_MAX_USERNAME_LENGTH as usize),
+            max_secret_len: Some(CRED_MAX_CREDENTIAL_BLOB_SIZE as usize),
+            ..Default::default()
+        }
+    }
 }
 
 fn extract_password(credential: &CREDENTIALW) -> Result<String> {
@@ -493,7 +513,7 @@ mod tests {
 
     use crate::Entry;
     use crate::credential::CredentialPersistence;
-    use crate::tests::{generate_random_string, generate_random_string_of_len};
+    use crate::testing::{generate_random_string, generate_random_string_of_len};
 
     #[test]
     fn test_persistence() {
@@ -503,8 +523,18 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn test_capabilities() {
+        let capabilities = default_credential_builder().capabilities();
+        assert!(capabilities.attributes && !capabilities.search);
+        assert_eq!(
+            capabilities.max_user_len,
+            Some(CRED_MAX_USERNAME_LENGTH as usize)
+        );
+    }
+
     fn entry_new(service: &str, user: &str) -> Entry {
-        crate::tests::entry_from_constructor(WinCredential::new_with_target, service, user)
+        crate::testing::entry_from_constructor(WinCredential::new_with_target, service, user)
     }
 
     #[test]
@@ -633,37 +663,37 @@ mod tests {
 
     #[test]
     fn test_empty_service_and_user() {
-        crate::tests::test_empty_service_and_user(entry_new);
+        crate::testing::test_empty_service_and_user(entry_new);
     }
 
     #[test]
     fn test_missing_entry() {
-        crate::tests::test_missing_entry(entry_new);
+        crate::testing::test_missing_entry(entry_new);
     }
 
     #[test]
     fn test_empty_password() {
-        crate::tests::test_empty_password(entry_new);
+        crate::testing::test_empty_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_ascii_password() {
-        crate::tests::test_round_trip_ascii_password(entry_new);
+        crate::testing::test_round_trip_ascii_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_non_ascii_password() {
-        crate::tests::test_round_trip_non_ascii_password(entry_new);
+        crate::testing::test_round_trip_non_ascii_password(entry_new);
     }
 
     #[test]
     fn test_round_trip_random_secret() {
-        crate::tests::test_round_trip_random_secret(entry_new);
+        crate::testing::test_round_trip_random_secret(entry_new);
     }
 
     #[test]
     fn test_update() {
-        crate::tests::test_update(entry_new);
+        crate::testing::test_update(entry_new);
     }
 
     #[test]
@@ -31,3 +31,14 @@ pub fn generate_random_bytes_of_len(len: usize) -> Vec<u8> {
 pub fn init_logger() {
     let _ = env_logger::builder().is_test(true).try_init();
 }
+
+/// Point the secret-service store at a fake, so tests don't need a running
+/// secret service.  Tests that call this must do so before anything else
+/// in their process connects to the session bus.
+#[cfg(all(target_os = "linux", feature = "fake-secret-service"))]
+pub fn install_fake() {
+    // SAFETY: tests call this before using the store,
+    // and nothing else in them reads the environment outside of Rust
+    unsafe { keyring::fake_secret_service::FakeSecretService::install() }
+        .expect("Can't start the fake secret service");
+}
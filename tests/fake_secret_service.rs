@@ -0,0 +1,51 @@
+#![cfg(all(target_os = "linux", feature = "fake-secret-service"))]
+
+use common::{generate_random_bytes_of_len, generate_random_string, init_logger, install_fake};
+use keyring::secret_service::{EncryptionType, SsCredentialBuilder};
+use keyring::{Entry, Error};
+
+mod common;
+
+/// Point the default store at the fake.  (The fake only supports plain sessions.)
+fn init() {
+    init_logger();
+    install_fake();
+    let builder = SsCredentialBuilder::with_encryption(EncryptionType::Plain);
+    keyring::set_default_credential_builder(Box::new(builder));
+}
+
+#[test]
+fn test_round_trip_password() {
+    init();
+
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name).expect("Can't create entry");
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    entry
+        .set_password("fake password")
+        .expect("Can't set password");
+    assert_eq!(
+        entry.get_password().expect("Can't get password"),
+        "fake password"
+    );
+    entry.delete_credential().expect("Can't delete password");
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_round_trip_secret_across_threads() {
+    init();
+
+    let name = generate_random_string();
+    let secret = generate_random_bytes_of_len(32);
+    let entry = Entry::new(&name, &name).expect("Can't create entry");
+    entry.set_secret(&secret).expect("Can't set secret");
+    let handle = std::thread::spawn(move || {
+        let entry = Entry::new(&name, &name).expect("Can't create entry");
+        let found = entry.get_secret().expect("Can't get secret");
+        entry.delete_credential().expect("Can't delete secret");
+        found
+    });
+    assert_eq!(handle.join().unwrap(), secret);
+    assert!(matches!(entry.get_secret(), Err(Error::NoEntry)));
+}